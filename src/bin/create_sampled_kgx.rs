@@ -1,13 +1,24 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use humantime::format_duration;
-use log::{debug, info};
+use itertools::Itertools;
+use log::{debug, info, warn};
 use polars::prelude::*;
-use rand::Rng;
-use rand::distr::Uniform;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{seq, Rng, SeedableRng};
+use rusty_matrix_io::{Adjacency, FileFormat};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::fs;
 use std::time::Instant;
 use std::{error, path};
 
+const DEFAULT_OUTPUT_TEMPLATE: &str = "{stem}_{size}.{ext}";
+
+fn default_output_template() -> String {
+    DEFAULT_OUTPUT_TEMPLATE.to_string()
+}
+
 #[derive(Parser, PartialEq, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Options {
@@ -20,11 +31,157 @@ struct Options {
     #[clap(short = 'o', long, required = true)]
     output_dir: path::PathBuf,
 
+    /// output file name relative to output_dir; supports {stem}, {kind}, {size}, {seed} and {ext}
+    #[clap(long, default_value = DEFAULT_OUTPUT_TEMPLATE)]
+    output_template: String,
+
+    #[clap(long)]
+    nodes_out: Option<path::PathBuf>,
+
+    #[clap(long)]
+    edges_out: Option<path::PathBuf>,
+
+    #[clap(short = 'f', long, value_enum, default_value_t = FileFormat::Tsv)]
+    output_format: FileFormat,
+
     #[clap(short = 's', long, default_value_t = 100)]
     size: u32,
 
     #[clap(short = 'r', long, default_value_t = false)]
     random: bool,
+
+    #[clap(short = 'd', long)]
+    seed: Option<u64>,
+
+    #[clap(short = 'm', long)]
+    manifest: Option<path::PathBuf>,
+
+    #[clap(short = 't', long, value_enum)]
+    stratify_by: Option<Stratum>,
+
+    /// per-stratum quota as <stratum>=<count>, e.g. biolink:treats=10; strata without a quota are allocated proportionally
+    #[clap(short = 'q', long)]
+    quota: Vec<String>,
+
+    #[clap(long, default_value_t = 1)]
+    min_per_stratum: usize,
+
+    #[clap(short = 'g', long, value_enum, conflicts_with = "stratify_by")]
+    sampler: Option<Sampler>,
+
+    /// stop once the sample has this many nodes; defaults to size when no target is given
+    #[clap(long)]
+    target_nodes: Option<usize>,
+
+    /// stop once the induced subgraph has this many edges
+    #[clap(long)]
+    target_edges: Option<usize>,
+
+    #[clap(long, default_value_t = 0.15)]
+    restart_probability: f64,
+
+    #[clap(long, default_value_t = 0.7)]
+    burn_probability: f64,
+
+    #[clap(long, default_value_t = 10)]
+    snowball_fan_out: usize,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sampler {
+    #[value(name = "random_walk")]
+    RandomWalk,
+    #[value(name = "forest_fire")]
+    ForestFire,
+    Snowball,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stratum {
+    Predicate,
+    Category,
+    #[value(name = "primary_knowledge_source")]
+    PrimaryKnowledgeSource,
+}
+
+impl Stratum {
+    fn column_name(&self) -> &str {
+        match self {
+            Stratum::Predicate => "predicate",
+            Stratum::Category => "category",
+            Stratum::PrimaryKnowledgeSource => "primary_knowledge_source",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stratification {
+    pub stratify_by: Stratum,
+    pub min_per_stratum: usize,
+    pub quotas: BTreeMap<String, usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StructuralSampling {
+    pub sampler: Sampler,
+    pub target_nodes: Option<usize>,
+    pub target_edges: Option<usize>,
+    pub restart_probability: f64,
+    pub burn_probability: f64,
+    pub snowball_fan_out: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct PreservationReport {
+    pub source_nodes: usize,
+    pub source_edges: usize,
+    pub sampled_nodes: usize,
+    pub sampled_edges: usize,
+    pub degree_ks_statistic: f64,
+    pub predicate_total_variation: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub path: String,
+    pub sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SampleCounts {
+    pub nodes_by_category: BTreeMap<String, usize>,
+    pub edges_by_predicate: BTreeMap<String, usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ManifestFiles {
+    pub nodes: ManifestFile,
+    pub edges: ManifestFile,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct SampleManifest {
+    pub seed: u64,
+    pub size: u32,
+    pub random: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stratification: Option<Stratification>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structural: Option<StructuralSampling>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preservation: Option<PreservationReport>,
+    #[serde(default)]
+    pub output_format: FileFormat,
+    #[serde(default = "default_output_template")]
+    pub output_template: String,
+    pub inputs: ManifestFiles,
+    pub outputs: ManifestFiles,
+    #[serde(default)]
+    pub counts: SampleCounts,
 }
 
 fn main() -> Result<(), Box<dyn error::Error>> {
@@ -34,104 +191,702 @@ fn main() -> Result<(), Box<dyn error::Error>> {
     let options = Options::parse();
     debug!("{:?}", options);
 
-    let edge_id_columns_df = LazyCsvReader::new(options.edges.clone())
+    sample(&options)?;
+
+    info!("Duration: {}", format_duration(start.elapsed()).to_string());
+    Ok(())
+}
+
+fn sample(options: &Options) -> Result<SampleManifest, Box<dyn error::Error>> {
+    let previous_manifest: Option<SampleManifest> = options.manifest.as_ref().map(|manifest_path| {
+        let manifest_content = fs::read_to_string(manifest_path).expect("Could not read manifest");
+        serde_json::from_str(&manifest_content).expect("Could not deserialize manifest")
+    });
+
+    let nodes_sha256 = rusty_matrix_io::sha256_digest(&options.nodes);
+    let edges_sha256 = rusty_matrix_io::sha256_digest(&options.edges);
+
+    // when replaying a manifest, the sampling parameters and outputs come from the manifest and the inputs must be the same bytes
+    let (seed, size, random, stratification, structural) = match &previous_manifest {
+        Some(manifest) => {
+            if manifest.inputs.nodes.sha256 != nodes_sha256 || manifest.inputs.edges.sha256 != edges_sha256 {
+                return Err(format!("Input files do not match the manifest: {:?}", manifest.inputs).into());
+            }
+            info!(
+                "replaying manifest with seed: {}, size: {}, random: {}",
+                manifest.seed, manifest.size, manifest.random
+            );
+            (
+                manifest.seed,
+                manifest.size,
+                manifest.random,
+                manifest.stratification.clone(),
+                manifest.structural.clone(),
+            )
+        }
+        None => (
+            options.seed.unwrap_or_else(rand::random),
+            options.size,
+            options.random,
+            options.stratify_by.map(|stratify_by| Stratification {
+                stratify_by,
+                min_per_stratum: options.min_per_stratum,
+                quotas: parse_quotas(&options.quota),
+            }),
+            options.sampler.map(|sampler| StructuralSampling {
+                sampler,
+                target_nodes: match (options.target_nodes, options.target_edges) {
+                    (None, None) => Some(options.size as usize),
+                    (target_nodes, _) => target_nodes,
+                },
+                target_edges: options.target_edges,
+                restart_probability: options.restart_probability,
+                burn_probability: options.burn_probability,
+                snowball_fan_out: options.snowball_fan_out,
+            }),
+        ),
+    };
+    info!("seed: {}", seed);
+
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let edges_lf = LazyCsvReader::new(options.edges.clone())
         .with_separator(b'\t')
         .with_truncate_ragged_lines(true)
         .with_has_header(true)
         .with_ignore_errors(true)
         .finish()
         .unwrap()
-        .select([col("subject"), col("object")])
-        .collect()
-        .unwrap();
+        .with_row_index("_row_index", None);
 
-    let edge_ids_df = concat(
-        [
-            edge_id_columns_df.clone().lazy().select([col("subject").alias("id")]),
-            edge_id_columns_df.clone().lazy().select([col("object").alias("id")]),
-        ],
-        UnionArgs::default(),
-    )
-    .unwrap()
-    .unique(None, UniqueKeepStrategy::First)
-    .collect()
-    .unwrap();
+    let mut preservation = None;
+
+    let mut edges_df = match (&structural, &stratification) {
+        (Some(structural), _) => {
+            let all_edges_df = edges_lf.collect().unwrap();
+            let adjacency = Adjacency::from_edges(&all_edges_df);
+            let sample = sample_structural(&adjacency, structural, &mut rng);
+            info!("{:?} sample: {} nodes, {} edges", structural.sampler, sample.nodes.len(), sample.edge_count);
 
-    let edge_ids_series = edge_ids_df.column("id").unwrap().as_series().unwrap();
+            let sampled_edge_indexes = sample.induced_edges(&adjacency).into_iter().map(|a| a as IdxSize).collect_vec();
+            let sampled_edges_df = all_edges_df.take(&IdxCa::from_vec("idx".into(), sampled_edge_indexes.clone())).unwrap();
 
-    let selected_ids_series = match options.random {
-        true => {
-            let mut rng = rand::rng();
-            let dist = Uniform::new(0, edge_ids_series.len() as i32).expect("Could not create Uniform from range");
-            let sampled_indexes: Vec<i32> = (&mut rng).sample_iter(dist).take(options.size.clone() as usize).collect();
-            let selected_ids: Vec<_> = sampled_indexes.iter().map(|a| edge_ids_series.get(*a as usize).unwrap().str_value()).collect();
-            Series::new("id".into(), selected_ids)
+            let report = report_preservation(&adjacency, &all_edges_df, &sampled_edge_indexes);
+            info!("{:?}", report);
+            preservation = Some(report);
+
+            sampled_edges_df.drop("_row_index").unwrap()
+        }
+        (None, Some(stratification)) if stratification.stratify_by != Stratum::Category => {
+            // edge strata are sampled as edge rows, so every sampled edge is kept as-is
+            let all_edges_df = edges_lf.collect().unwrap();
+            sample_stratified(&all_edges_df, stratification, size as usize, &mut rng)
+                .drop("_row_index")
+                .unwrap()
+        }
+        (None, Some(stratification)) => {
+            let edge_ids_df = select_edge_ids(edges_lf.clone());
+            let nodes_category_df = LazyCsvReader::new(options.nodes.clone())
+                .with_separator(b'\t')
+                .with_truncate_ragged_lines(true)
+                .with_has_header(true)
+                .with_ignore_errors(true)
+                .finish()
+                .unwrap()
+                .select([col("id"), col("category")])
+                .semi_join(edge_ids_df.lazy(), col("id"), col("id"))
+                .unique_stable(Some(vec!["id".into()]), UniqueKeepStrategy::First)
+                .collect()
+                .unwrap();
+            let selected_ids_df = sample_stratified(&nodes_category_df, stratification, size as usize, &mut rng)
+                .select(["id"])
+                .unwrap();
+            // only edges between sampled nodes are kept, so no node outside the category quotas is pulled in
+            select_edges_within(edges_lf, selected_ids_df.lazy())
+        }
+        (None, None) => {
+            let edge_ids_df = select_edge_ids(edges_lf.clone());
+            let edge_ids_series = edge_ids_df.column("id").unwrap().as_series().unwrap();
+
+            let selected_ids_series = match random {
+                true => {
+                    let amount = (size as usize).min(edge_ids_series.len());
+                    let mut sampled_indexes = seq::index::sample(&mut rng, edge_ids_series.len(), amount).into_vec();
+                    sampled_indexes.sort();
+                    let selected_ids: Vec<_> = sampled_indexes.iter().map(|a| edge_ids_series.get(*a).unwrap().str_value()).collect();
+                    Series::new("id".into(), selected_ids)
+                }
+                false => edge_ids_series.limit(size as usize),
+            };
+            select_edges_touching(edges_lf, selected_ids_series.into_frame().lazy())
         }
-        false => edge_ids_series.limit(options.size.clone() as usize),
     };
 
-    let mut edges_df = LazyCsvReader::new(options.edges.clone())
+    let selected_edge_ids_df = select_edge_ids(edges_df.clone().lazy());
+
+    let mut nodes_df = LazyCsvReader::new(options.nodes.clone())
         .with_separator(b'\t')
         .with_truncate_ragged_lines(true)
         .with_has_header(true)
         .with_ignore_errors(true)
         .finish()
         .unwrap()
-        .filter(
-            col("subject")
-                .str()
-                .contains_any(lit(selected_ids_series.clone()), false)
-                .or(col("object").str().contains_any(lit(selected_ids_series.clone()), false)),
-        )
+        .semi_join(selected_edge_ids_df.lazy(), col("id"), col("id"))
+        .unique_stable(Some(vec!["id".into()]), UniqueKeepStrategy::First)
         .collect()
         .unwrap();
 
-    let mut edges_file_name = options.nodes.clone().file_name().unwrap().to_str().unwrap().to_string();
-    edges_file_name = edges_file_name.replace("nodes.", format!("edges_{}.", options.size.clone()).as_str());
+    edges_df = rusty_matrix_io::close_subgraph(edges_df, &nodes_df);
+
+    let (output_format, output_template) = match &previous_manifest {
+        Some(manifest) => (manifest.output_format, manifest.output_template.clone()),
+        None => (options.output_format, options.output_template.clone()),
+    };
+    let (nodes_output, edges_output) = match &previous_manifest {
+        Some(manifest) => (
+            path::PathBuf::from(&manifest.outputs.nodes.path),
+            path::PathBuf::from(&manifest.outputs.edges.path),
+        ),
+        None => (
+            options.nodes_out.clone().unwrap_or_else(|| {
+                options
+                    .output_dir
+                    .join(render_output_name(&output_template, &options.nodes, "nodes", size, seed, output_format))
+            }),
+            options.edges_out.clone().unwrap_or_else(|| {
+                options
+                    .output_dir
+                    .join(render_output_name(&output_template, &options.edges, "edges", size, seed, output_format))
+            }),
+        ),
+    };
+    debug!("nodes_output: {:?}, edges_output: {:?}", nodes_output, edges_output);
+
+    if nodes_output == edges_output {
+        return Err(format!("Nodes and edges outputs resolve to the same path: {:?}", nodes_output).into());
+    }
+
+    for output_dir in [Some(options.output_dir.as_path()), nodes_output.parent(), edges_output.parent()]
+        .into_iter()
+        .flatten()
+    {
+        fs::create_dir_all(output_dir).expect("Could not create output directory");
+    }
+
+    rusty_matrix_io::write_dataframe(&mut nodes_df, &nodes_output, output_format);
+    rusty_matrix_io::write_dataframe(&mut edges_df, &edges_output, output_format);
+
+    let counts = SampleCounts {
+        nodes_by_category: rusty_matrix_io::count_by(&nodes_df, "category").unwrap_or_default(),
+        edges_by_predicate: rusty_matrix_io::count_by(&edges_df, "predicate").unwrap_or_default(),
+    };
 
-    debug!("edges_file_name: {}", edges_file_name);
-    let mut output_edges_file = fs::File::create(format!("{}/{}", options.output_dir.to_string_lossy(), edges_file_name)).unwrap();
-    CsvWriter::new(&mut output_edges_file).with_separator(b'\t').finish(&mut edges_df).unwrap();
+    let manifest = SampleManifest {
+        seed,
+        size,
+        random,
+        stratification,
+        structural,
+        preservation,
+        output_format,
+        output_template,
+        inputs: ManifestFiles {
+            nodes: ManifestFile {
+                path: options.nodes.to_string_lossy().to_string(),
+                sha256: nodes_sha256,
+                rows: None,
+            },
+            edges: ManifestFile {
+                path: options.edges.to_string_lossy().to_string(),
+                sha256: edges_sha256,
+                rows: None,
+            },
+        },
+        outputs: ManifestFiles {
+            nodes: ManifestFile {
+                path: nodes_output.to_string_lossy().to_string(),
+                sha256: rusty_matrix_io::sha256_digest(&nodes_output),
+                rows: Some(nodes_df.height()),
+            },
+            edges: ManifestFile {
+                path: edges_output.to_string_lossy().to_string(),
+                sha256: rusty_matrix_io::sha256_digest(&edges_output),
+                rows: Some(edges_df.height()),
+            },
+        },
+        counts,
+    };
+
+    if let Some(previous_manifest) = &previous_manifest {
+        match previous_manifest.outputs.nodes.sha256 == manifest.outputs.nodes.sha256 && previous_manifest.outputs.edges.sha256 == manifest.outputs.edges.sha256
+        {
+            true => info!("outputs are byte-identical to the manifest"),
+            false => warn!("outputs differ from the manifest: {:?}", previous_manifest.outputs),
+        }
+    }
 
-    let selected_edge_ids_df = concat(
+    let manifest_output = options.output_dir.join("sample_manifest.json");
+    fs::write(manifest_output, serde_json::to_string_pretty(&manifest).unwrap()).expect("Could not write manifest");
+
+    Ok(manifest)
+}
+
+fn render_output_name(template: &str, input: &path::PathBuf, kind: &str, size: u32, seed: u64, format: FileFormat) -> String {
+    let file_name = input.file_name().unwrap().to_string_lossy().to_string();
+    let stem = file_name.split('.').next().unwrap_or(kind);
+    template
+        .replace("{stem}", stem)
+        .replace("{kind}", kind)
+        .replace("{size}", size.to_string().as_str())
+        .replace("{seed}", seed.to_string().as_str())
+        .replace("{ext}", format.extension())
+}
+
+fn parse_quotas(quotas: &[String]) -> BTreeMap<String, usize> {
+    quotas
+        .iter()
+        .map(|quota| {
+            let (stratum, count) = quota.rsplit_once('=').expect("Quota must be formatted as <stratum>=<count>");
+            (stratum.to_string(), count.parse().expect("Quota count must be a non-negative integer"))
+        })
+        .collect()
+}
+
+fn select_edge_ids(edges_lf: LazyFrame) -> DataFrame {
+    concat(
         [
-            edges_df.clone().lazy().select([col("subject").alias("id")]),
-            edges_df.clone().lazy().select([col("object").alias("id")]),
+            edges_lf.clone().select([col("subject").alias("id")]),
+            edges_lf.clone().select([col("object").alias("id")]),
         ],
         UnionArgs::default(),
     )
     .unwrap()
+    .unique_stable(None, UniqueKeepStrategy::First)
     .collect()
-    .unwrap();
-    let selected_edge_ids = selected_edge_ids_df.column("id").unwrap().as_series().unwrap().clone();
+    .unwrap()
+}
 
-    let mut nodes_df = LazyCsvReader::new(options.nodes.clone())
-        .with_separator(b'\t')
-        .with_truncate_ragged_lines(true)
-        .with_has_header(true)
-        .with_ignore_errors(true)
-        .finish()
-        .unwrap()
-        .filter(col("id").str().contains_any(lit(selected_edge_ids.clone()), false))
+fn select_edges_touching(edges_lf: LazyFrame, selected_ids_df: LazyFrame) -> DataFrame {
+    // exact membership on either endpoint; the row index keeps the edges in file order after the union
+    concat(
+        [
+            edges_lf.clone().semi_join(selected_ids_df.clone(), col("subject"), col("id")),
+            edges_lf.clone().semi_join(selected_ids_df.clone(), col("object"), col("id")),
+        ],
+        UnionArgs::default(),
+    )
+    .unwrap()
+    .unique_stable(Some(vec!["_row_index".into()]), UniqueKeepStrategy::First)
+    .sort(["_row_index"], SortMultipleOptions::default())
+    .drop(["_row_index"])
+    .collect()
+    .unwrap()
+}
+
+fn select_edges_within(edges_lf: LazyFrame, selected_ids_df: LazyFrame) -> DataFrame {
+    edges_lf
+        .semi_join(selected_ids_df.clone(), col("subject"), col("id"))
+        .semi_join(selected_ids_df, col("object"), col("id"))
+        .sort(["_row_index"], SortMultipleOptions::default())
+        .drop(["_row_index"])
         .collect()
-        .unwrap();
+        .unwrap()
+}
 
-    let mut nodes_file_name = options.nodes.clone().file_name().unwrap().to_str().unwrap().to_string();
-    nodes_file_name = nodes_file_name.replace("nodes.", format!("nodes_{}.", options.size.clone()).as_str());
+fn sample_stratified(df: &DataFrame, stratification: &Stratification, size: usize, rng: &mut StdRng) -> DataFrame {
+    rusty_matrix_io::sample_stratified(
+        df,
+        stratification.stratify_by.column_name(),
+        size,
+        stratification.min_per_stratum,
+        &stratification.quotas,
+        rng,
+    )
+}
 
-    debug!("nodes_file_name: {}", nodes_file_name);
-    let mut output_nodes_file = fs::File::create(format!("{}/{}", options.output_dir.to_string_lossy(), nodes_file_name)).unwrap();
-    CsvWriter::new(&mut output_nodes_file).with_separator(9u8).finish(&mut nodes_df).unwrap();
+#[derive(Debug, Default)]
+struct InducedSample {
+    nodes: HashSet<usize>,
+    edge_count: usize,
+}
 
-    info!("Duration: {}", format_duration(start.elapsed()).to_string());
-    Ok(())
+impl InducedSample {
+    fn add(&mut self, adjacency: &Adjacency, node: usize) -> bool {
+        if !self.nodes.insert(node) {
+            return false;
+        }
+        self.edge_count += adjacency.incident[node]
+            .iter()
+            .filter(|edge| self.nodes.contains(&adjacency.neighbor(node, **edge)))
+            .count();
+        true
+    }
+
+    fn is_full(&self, structural: &StructuralSampling) -> bool {
+        structural.target_nodes.is_some_and(|target| self.nodes.len() >= target) || structural.target_edges.is_some_and(|target| self.edge_count >= target)
+    }
+
+    fn induced_edges(&self, adjacency: &Adjacency) -> Vec<usize> {
+        adjacency
+            .edges
+            .iter()
+            .enumerate()
+            .filter(|(_, (subject, object))| self.nodes.contains(subject) && self.nodes.contains(object))
+            .map(|(idx, _)| idx)
+            .collect_vec()
+    }
+}
+
+/// Start nodes come from one shuffle of all nodes, so restarts skip over sampled nodes instead of rescanning the graph.
+struct StartNodes {
+    order: Vec<usize>,
+    next: usize,
+}
+
+impl StartNodes {
+    fn new(adjacency: &Adjacency, rng: &mut StdRng) -> StartNodes {
+        let mut order = (0..adjacency.node_ids.len()).collect_vec();
+        order.shuffle(rng);
+        StartNodes { order, next: 0 }
+    }
+
+    fn next(&mut self, sample: &InducedSample) -> Option<usize> {
+        while let Some(node) = self.order.get(self.next) {
+            self.next += 1;
+            if !sample.nodes.contains(node) {
+                return Some(*node);
+            }
+        }
+        None
+    }
+}
+
+fn sample_structural(adjacency: &Adjacency, structural: &StructuralSampling, rng: &mut StdRng) -> InducedSample {
+    let mut sample = InducedSample::default();
+    let mut start_nodes = StartNodes::new(adjacency, rng);
+
+    match structural.sampler {
+        Sampler::RandomWalk => {
+            // a walk that stops discovering nodes is stuck in a small component, so it jumps to a new start
+            let max_stale_steps = 100 * adjacency.node_ids.len().clamp(1, 1000);
+            while !sample.is_full(structural) {
+                let Some(start) = start_nodes.next(&sample) else {
+                    break;
+                };
+                sample.add(adjacency, start);
+                let mut current = start;
+                let mut stale_steps = 0;
+                while !sample.is_full(structural) && stale_steps < max_stale_steps {
+                    let incident = &adjacency.incident[current];
+                    current = match incident.is_empty() || rng.random_bool(structural.restart_probability) {
+                        true => start,
+                        false => adjacency.neighbor(current, incident[rng.random_range(0..incident.len())]),
+                    };
+                    match sample.add(adjacency, current) {
+                        true => stale_steps = 0,
+                        false => stale_steps += 1,
+                    }
+                }
+            }
+        }
+        Sampler::ForestFire | Sampler::Snowball => {
+            while !sample.is_full(structural) {
+                let Some(start) = start_nodes.next(&sample) else {
+                    break;
+                };
+                sample.add(adjacency, start);
+                let mut queue = VecDeque::from([start]);
+                while let Some(current) = queue.pop_front() {
+                    if sample.is_full(structural) {
+                        break;
+                    }
+                    let neighbors = adjacency.incident[current]
+                        .iter()
+                        .map(|edge| adjacency.neighbor(current, *edge))
+                        .filter(|neighbor| !sample.nodes.contains(neighbor))
+                        .unique()
+                        .collect_vec();
+
+                    let amount = match structural.sampler {
+                        Sampler::ForestFire => {
+                            // geometrically distributed burn count with mean p / (1 - p)
+                            let mut burned = 0;
+                            while burned < neighbors.len() && rng.random_bool(structural.burn_probability) {
+                                burned += 1;
+                            }
+                            burned
+                        }
+                        _ => structural.snowball_fan_out.min(neighbors.len()),
+                    };
+
+                    for idx in seq::index::sample(rng, neighbors.len(), amount).into_iter().sorted() {
+                        if sample.is_full(structural) {
+                            break;
+                        }
+                        sample.add(adjacency, neighbors[idx]);
+                        queue.push_back(neighbors[idx]);
+                    }
+                }
+            }
+        }
+    }
+
+    sample
+}
+
+fn ks_statistic(source: &[usize], sampled: &[usize]) -> f64 {
+    if source.is_empty() || sampled.is_empty() {
+        return 0.0;
+    }
+    let source = source.iter().sorted().collect_vec();
+    let sampled = sampled.iter().sorted().collect_vec();
+    let (mut i, mut j, mut max_distance) = (0, 0, 0.0_f64);
+    while i < source.len() && j < sampled.len() {
+        let value = source[i].min(sampled[j]);
+        while i < source.len() && source[i] == value {
+            i += 1;
+        }
+        while j < sampled.len() && sampled[j] == value {
+            j += 1;
+        }
+        max_distance = max_distance.max((i as f64 / source.len() as f64 - j as f64 / sampled.len() as f64).abs());
+    }
+    max_distance
+}
+
+fn total_variation(source: &BTreeMap<String, usize>, sampled: &BTreeMap<String, usize>) -> f64 {
+    let source_total = source.values().sum::<usize>().max(1) as f64;
+    let sampled_total = sampled.values().sum::<usize>().max(1) as f64;
+    source
+        .keys()
+        .chain(sampled.keys())
+        .unique()
+        .map(|k| (*source.get(k).unwrap_or(&0) as f64 / source_total - *sampled.get(k).unwrap_or(&0) as f64 / sampled_total).abs())
+        .sum::<f64>()
+        / 2.0
+}
+
+fn report_preservation(adjacency: &Adjacency, edges_df: &DataFrame, sampled_edge_indexes: &[IdxSize]) -> PreservationReport {
+    let source_degrees = (0..adjacency.node_ids.len()).map(|node| adjacency.degree(node)).collect_vec();
+
+    let mut sampled_degree_by_node: BTreeMap<usize, usize> = BTreeMap::new();
+    sampled_edge_indexes.iter().for_each(|edge| {
+        let (subject, object) = adjacency.edges[*edge as usize];
+        *sampled_degree_by_node.entry(subject).or_default() += 1;
+        if subject != object {
+            *sampled_degree_by_node.entry(object).or_default() += 1;
+        }
+    });
+    let sampled_degrees = sampled_degree_by_node.values().cloned().collect_vec();
+
+    let predicates = edges_df.column("predicate").unwrap().str().unwrap().clone();
+    let predicate_counts = |indexes: &mut dyn Iterator<Item = usize>| -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        indexes.for_each(|idx| *counts.entry(predicates.get(idx).unwrap_or("null").to_string()).or_default() += 1);
+        counts
+    };
+    let source_predicates = predicate_counts(&mut (0..edges_df.height()));
+    let sampled_predicates = predicate_counts(&mut sampled_edge_indexes.iter().map(|a| *a as usize));
+
+    PreservationReport {
+        source_nodes: adjacency.node_ids.len(),
+        source_edges: adjacency.edges.len(),
+        sampled_nodes: sampled_degrees.len(),
+        sampled_edges: sampled_edge_indexes.len(),
+        degree_ks_statistic: ks_statistic(&source_degrees, &sampled_degrees),
+        predicate_total_variation: total_variation(&source_predicates, &sampled_predicates),
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use clap::Parser;
+    use itertools::Itertools;
     use polars::prelude::*;
-    use rand::Rng;
     use rand::distr::Uniform;
+    use rand::rngs::StdRng;
+    use rand::Rng;
+    use rand::SeedableRng;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_close_subgraph() {
+        let nodes_df = df!("id" => ["MONDO:1", "MONDO:10", "CHEBI:1"]).unwrap();
+        let edges_df = df!(
+            "subject" => ["CHEBI:1", "CHEBI:1", "CHEBI:2"],
+            "predicate" => ["biolink:treats", "biolink:treats", "biolink:treats"],
+            "object" => ["MONDO:1", "MONDO:100", "MONDO:10"],
+        )
+        .unwrap();
+
+        let closed_edges_df = rusty_matrix_io::close_subgraph(edges_df, &nodes_df);
+        assert_eq!(closed_edges_df.height(), 1);
+        assert_eq!(closed_edges_df.column("object").unwrap().str().unwrap().get(0), Some("MONDO:1"));
+    }
+
+    #[test]
+    fn test_select_edges_within() {
+        let edges_df = df!(
+            "subject" => ["CHEBI:1", "CHEBI:1", "CHEBI:2", "MONDO:1"],
+            "predicate" => ["biolink:treats"; 4],
+            "object" => ["MONDO:1", "MONDO:2", "MONDO:1", "CHEBI:1"],
+        )
+        .unwrap();
+        let selected_ids_df = df!("id" => ["CHEBI:1", "MONDO:1"]).unwrap();
+
+        let edges_lf = edges_df.lazy().with_row_index("_row_index", None);
+        let selected_edges_df = crate::select_edges_within(edges_lf, selected_ids_df.clone().lazy());
+        assert_eq!(selected_edges_df.height(), 2);
+
+        let endpoints = ["subject", "object"]
+            .iter()
+            .flat_map(|c| {
+                selected_edges_df
+                    .column(c)
+                    .unwrap()
+                    .str()
+                    .unwrap()
+                    .into_no_null_iter()
+                    .map(|a| a.to_string())
+                    .collect_vec()
+            })
+            .collect_vec();
+        assert!(endpoints.iter().all(|id| ["CHEBI:1", "MONDO:1"].contains(&id.as_str())));
+    }
+
+    #[test]
+    fn test_allocate_quotas() {
+        let stratum_sizes = BTreeMap::from([
+            ("biolink:treats".to_string(), 900),
+            ("biolink:contraindicated_in".to_string(), 10),
+            ("biolink:interacts_with".to_string(), 90),
+        ]);
+        let quotas = BTreeMap::from([("biolink:interacts_with".to_string(), 5)]);
+
+        let allocation = rusty_matrix_io::allocate_quotas(&stratum_sizes, 100, 3, &quotas);
+        assert_eq!(allocation.get("biolink:treats"), Some(&90));
+        assert_eq!(allocation.get("biolink:contraindicated_in"), Some(&3));
+        assert_eq!(allocation.get("biolink:interacts_with"), Some(&5));
+    }
+
+    #[test]
+    fn test_render_output_name() {
+        let nodes = std::path::PathBuf::from("/data/rtx_kg2_nodes.tsv.gz");
+        let name = crate::render_output_name("{stem}_{size}.{ext}", &nodes, "nodes", 100, 7, rusty_matrix_io::FileFormat::Parquet);
+        assert_eq!(name, "rtx_kg2_nodes_100.parquet");
+
+        let name = crate::render_output_name("sample_{seed}/{kind}.{ext}", &nodes, "nodes", 100, 7, rusty_matrix_io::FileFormat::Tsv);
+        assert_eq!(name, "sample_7/nodes.tsv");
+    }
+
+    #[test]
+    fn test_preservation_statistics() {
+        assert_eq!(crate::ks_statistic(&[1, 2, 3, 4], &[1, 2, 3, 4]), 0.0);
+        assert_eq!(crate::ks_statistic(&[1, 1, 2, 2], &[1, 1, 1, 1]), 0.5);
+
+        let source = BTreeMap::from([("biolink:treats".to_string(), 50), ("biolink:interacts_with".to_string(), 50)]);
+        let sampled = BTreeMap::from([("biolink:treats".to_string(), 10)]);
+        assert_eq!(crate::total_variation(&source, &sampled), 0.5);
+    }
+
+    #[test]
+    fn test_random_walk_sampler() {
+        let edges_df = df!(
+            "subject" => ["A:1", "A:2", "A:3", "A:4", "A:5", "A:6", "A:7"],
+            "predicate" => ["biolink:related_to"; 7],
+            "object" => ["A:2", "A:3", "A:4", "A:1", "A:6", "A:7", "A:8"],
+        )
+        .unwrap();
+        let adjacency = rusty_matrix_io::Adjacency::from_edges(&edges_df);
+        let structural = crate::StructuralSampling {
+            sampler: crate::Sampler::RandomWalk,
+            target_nodes: Some(5),
+            target_edges: None,
+            restart_probability: 0.15,
+            burn_probability: 0.7,
+            snowball_fan_out: 10,
+        };
+
+        let sample_nodes = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            crate::sample_structural(&adjacency, &structural, &mut rng)
+                .nodes
+                .into_iter()
+                .sorted()
+                .collect_vec()
+        };
+
+        // the walk restarts into the second component once the first one is exhausted
+        let nodes = sample_nodes(42);
+        assert_eq!(nodes.len(), 5);
+        assert_eq!(nodes, sample_nodes(42));
+    }
+
+    #[test]
+    fn test_manifest_replay() {
+        let test_dir = std::env::temp_dir().join("create_sampled_kgx_test_manifest_replay");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let nodes = test_dir.join("nodes.tsv");
+        let edges = test_dir.join("edges.tsv");
+        std::fs::write(
+            &nodes,
+            "id\tcategory\nA:1\tbiolink:Gene\nA:2\tbiolink:Gene\nA:3\tbiolink:Disease\nA:4\tbiolink:Disease\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &edges,
+            "subject\tpredicate\tobject\nA:1\tbiolink:related_to\tA:3\nA:2\tbiolink:related_to\tA:4\nA:1\tbiolink:related_to\tA:2\n",
+        )
+        .unwrap();
+
+        let sample_dir = test_dir.join("sample");
+        let options = crate::Options::parse_from([
+            "create_sampled_kgx",
+            "-n",
+            nodes.to_str().unwrap(),
+            "-e",
+            edges.to_str().unwrap(),
+            "-o",
+            sample_dir.to_str().unwrap(),
+            "-s",
+            "2",
+            "-r",
+            "-d",
+            "7",
+        ]);
+        let manifest = crate::sample(&options).unwrap();
+        let nodes_bytes = std::fs::read(&manifest.outputs.nodes.path).unwrap();
+        let edges_bytes = std::fs::read(&manifest.outputs.edges.path).unwrap();
+        std::fs::remove_file(&manifest.outputs.nodes.path).unwrap();
+        std::fs::remove_file(&manifest.outputs.edges.path).unwrap();
+
+        // flags that would change the output are ignored in favour of the manifest
+        let replay_dir = test_dir.join("replay");
+        let replay_options = crate::Options::parse_from([
+            "create_sampled_kgx",
+            "-n",
+            nodes.to_str().unwrap(),
+            "-e",
+            edges.to_str().unwrap(),
+            "-o",
+            replay_dir.to_str().unwrap(),
+            "-m",
+            sample_dir.join("sample_manifest.json").to_str().unwrap(),
+            "-f",
+            "jsonl",
+            "--output-template",
+            "{kind}_{seed}.{ext}",
+            "-s",
+            "3",
+        ]);
+        let replayed_manifest = crate::sample(&replay_options).unwrap();
+        assert_eq!(replayed_manifest.outputs, manifest.outputs);
+        assert_eq!(std::fs::read(&replayed_manifest.outputs.nodes.path).unwrap(), nodes_bytes);
+        assert_eq!(std::fs::read(&replayed_manifest.outputs.edges.path).unwrap(), edges_bytes);
+
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
 
     #[test]
     fn test_random_edges() {
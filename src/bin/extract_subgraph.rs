@@ -0,0 +1,304 @@
+use clap::Parser;
+use humantime::format_duration;
+use itertools::Itertools;
+use log::{debug, info, warn};
+use polars::prelude::*;
+use rand::rngs::StdRng;
+use rand::{seq, SeedableRng};
+use rusty_matrix_io::Adjacency;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::time::Instant;
+use std::{error, fs, path};
+
+#[derive(Parser, PartialEq, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Options {
+    #[clap(short = 'n', long, required = true)]
+    nodes: path::PathBuf,
+
+    #[clap(short = 'e', long, required = true)]
+    edges: path::PathBuf,
+
+    /// file with one CURIE per line
+    #[clap(short = 's', long, required = true)]
+    seeds: path::PathBuf,
+
+    #[clap(short = 'k', long, default_value_t = 1)]
+    hops: usize,
+
+    #[clap(short = 'p', long)]
+    predicates: Option<Vec<String>>,
+
+    #[clap(short = 'c', long)]
+    categories: Option<Vec<String>>,
+
+    /// maximum number of incident edges followed out of any one node
+    #[clap(short = 'f', long)]
+    max_fan_out: Option<usize>,
+
+    /// seed for choosing which edges are followed when the fan-out is capped
+    #[clap(short = 'd', long)]
+    seed: Option<u64>,
+
+    #[clap(short = 'x', long, required = true)]
+    nodes_out: path::PathBuf,
+
+    #[clap(short = 'y', long, required = true)]
+    edges_out: path::PathBuf,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct HopReport {
+    pub hop: usize,
+    pub frontier: usize,
+    pub nodes_added: usize,
+    pub edges_added: usize,
+    pub fan_out_capped: usize,
+}
+
+fn main() -> Result<(), Box<dyn error::Error>> {
+    let start = Instant::now();
+    env_logger::init();
+
+    let options = Options::parse();
+    debug!("{:?}", options);
+
+    let seeds = fs::read_to_string(&options.seeds)
+        .expect("Could not read seeds file")
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .unique()
+        .collect_vec();
+    info!("seeds: {}", seeds.len());
+
+    let mut edges_lf = LazyCsvReader::new(options.edges.clone())
+        .with_separator(b'\t')
+        .with_infer_schema_length(Some(0))
+        .with_truncate_ragged_lines(true)
+        .with_has_header(true)
+        .with_ignore_errors(true)
+        .finish()
+        .unwrap();
+
+    if let Some(predicates) = &options.predicates {
+        let predicates_df = Series::new("predicate".into(), predicates.clone()).into_frame().lazy();
+        edges_lf = edges_lf.semi_join(predicates_df, col("predicate"), col("predicate"));
+    }
+
+    let edges_df = edges_lf.collect().unwrap();
+    let adjacency = Adjacency::from_edges(&edges_df);
+    info!("adjacency: {} nodes, {} edges", adjacency.node_ids.len(), adjacency.edges.len());
+
+    let nodes_lf = LazyCsvReader::new(options.nodes.clone())
+        .with_separator(b'\t')
+        .with_infer_schema_length(Some(0))
+        .with_truncate_ragged_lines(true)
+        .with_has_header(true)
+        .with_ignore_errors(true)
+        .finish()
+        .unwrap();
+
+    // category values may be multivalued, so a node is allowed if any of its categories is in the allow-list
+    let allowed_nodes: Option<HashSet<usize>> = options.categories.as_ref().map(|categories| {
+        let nodes_category_df = nodes_lf.clone().select([col("id"), col("category")]).collect().unwrap();
+        let ids = nodes_category_df.column("id").unwrap().str().unwrap().clone();
+        let node_categories = nodes_category_df.column("category").unwrap().str().unwrap().clone();
+        let separators: &[char] = &['|', char::from_u32(0x0000001F).unwrap()];
+        ids.into_iter()
+            .zip(&node_categories)
+            .filter_map(|(id, category)| match (id, category) {
+                (Some(id), Some(category)) if category.split(separators).any(|c| categories.iter().any(|a| a.eq(c))) => adjacency.node_index.get(id).cloned(),
+                _ => None,
+            })
+            .collect()
+    });
+
+    let seed_nodes = seeds
+        .iter()
+        .filter_map(|seed| {
+            let idx = adjacency.node_index.get(seed).cloned();
+            if idx.is_none() {
+                warn!("seed has no edges: {}", seed);
+            }
+            idx
+        })
+        .collect_vec();
+
+    let rng_seed = options.seed.unwrap_or_else(rand::random);
+    info!("rng seed: {}", rng_seed);
+    let mut rng = StdRng::seed_from_u64(rng_seed);
+
+    // only traversed edges are written, so capped hubs stay capped and the hop reports add up to the output
+    let (visited, selected_edges, hop_reports) = expand_neighborhood(&adjacency, &seed_nodes, options.hops, &allowed_nodes, options.max_fan_out, &mut rng);
+
+    let selected_edge_indexes = selected_edges.into_iter().map(|a| a as IdxSize).collect_vec();
+    let mut subgraph_edges_df = edges_df.take(&IdxCa::from_vec("idx".into(), selected_edge_indexes)).unwrap();
+
+    // seeds without edges are still part of the neighborhood if they exist in the nodes file
+    let selected_ids = visited
+        .iter()
+        .map(|a| adjacency.node_ids[*a].clone())
+        .chain(seeds.iter().cloned())
+        .unique()
+        .collect_vec();
+    let selected_ids_df = Series::new("id".into(), selected_ids).into_frame().lazy();
+
+    let mut subgraph_nodes_df = nodes_lf
+        .semi_join(selected_ids_df, col("id"), col("id"))
+        .unique_stable(Some(vec!["id".into()]), UniqueKeepStrategy::First)
+        .collect()
+        .unwrap();
+
+    subgraph_edges_df = rusty_matrix_io::close_subgraph(subgraph_edges_df, &subgraph_nodes_df);
+
+    info!("subgraph: {} nodes, {} edges", subgraph_nodes_df.height(), subgraph_edges_df.height());
+
+    for output in [&options.nodes_out, &options.edges_out] {
+        if let Some(parent_dir) = output.parent() {
+            fs::create_dir_all(parent_dir).expect("Could not create parent directory");
+        }
+    }
+
+    let mut nodes_file = fs::File::create(options.nodes_out.as_path()).unwrap();
+    CsvWriter::new(&mut nodes_file).with_separator(b'\t').finish(&mut subgraph_nodes_df).unwrap();
+
+    let mut edges_file = fs::File::create(options.edges_out.as_path()).unwrap();
+    CsvWriter::new(&mut edges_file).with_separator(b'\t').finish(&mut subgraph_edges_df).unwrap();
+
+    println!("{}", serde_json::to_string_pretty(&hop_reports).unwrap());
+
+    info!("Duration: {}", format_duration(start.elapsed()));
+    Ok(())
+}
+
+fn expand_neighborhood(
+    adjacency: &Adjacency,
+    seed_nodes: &[usize],
+    hops: usize,
+    allowed_nodes: &Option<HashSet<usize>>,
+    max_fan_out: Option<usize>,
+    rng: &mut StdRng,
+) -> (HashSet<usize>, BTreeSet<usize>, Vec<HopReport>) {
+    let mut visited: HashSet<usize> = HashSet::new();
+    let mut frontier = seed_nodes.iter().filter(|idx| visited.insert(**idx)).cloned().collect_vec();
+
+    let mut traversed_edges: BTreeSet<usize> = BTreeSet::new();
+    let mut hop_reports = vec![];
+
+    for hop in 1..=hops {
+        let mut report = HopReport {
+            hop,
+            frontier: frontier.len(),
+            ..Default::default()
+        };
+        let mut next_frontier = vec![];
+
+        for node in frontier.iter() {
+            let incident = adjacency.incident[*node]
+                .iter()
+                .filter(|edge| match allowed_nodes {
+                    Some(allowed) => allowed.contains(&adjacency.neighbor(*node, **edge)),
+                    None => true,
+                })
+                .cloned()
+                .collect_vec();
+
+            // capped edges are drawn at random so the result does not depend on how the edges file is sorted
+            let followed = match max_fan_out {
+                Some(max_fan_out) if incident.len() > max_fan_out => {
+                    report.fan_out_capped += 1;
+                    seq::index::sample(rng, incident.len(), max_fan_out)
+                        .into_iter()
+                        .sorted()
+                        .map(|idx| incident[idx])
+                        .collect_vec()
+                }
+                _ => incident,
+            };
+
+            for edge in followed.into_iter() {
+                if traversed_edges.insert(edge) {
+                    report.edges_added += 1;
+                }
+                let neighbor = adjacency.neighbor(*node, edge);
+                if visited.insert(neighbor) {
+                    report.nodes_added += 1;
+                    next_frontier.push(neighbor);
+                }
+            }
+        }
+
+        info!("{:?}", report);
+        hop_reports.push(report);
+        frontier = next_frontier;
+        if frontier.is_empty() {
+            break;
+        }
+    }
+
+    (visited, traversed_edges, hop_reports)
+}
+
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+    use polars::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use rusty_matrix_io::Adjacency;
+    use std::collections::HashSet;
+
+    fn adjacency(edges: &[(&str, &str)]) -> Adjacency {
+        let edges_df = df!(
+            "subject" => edges.iter().map(|(subject, _)| *subject).collect_vec(),
+            "predicate" => edges.iter().map(|_| "biolink:related_to").collect_vec(),
+            "object" => edges.iter().map(|(_, object)| *object).collect_vec(),
+        )
+        .unwrap();
+        Adjacency::from_edges(&edges_df)
+    }
+
+    fn node_ids(adjacency: &Adjacency, nodes: &HashSet<usize>) -> Vec<String> {
+        nodes.iter().map(|idx| adjacency.node_ids[*idx].clone()).sorted().collect_vec()
+    }
+
+    #[test]
+    fn test_hop_limit() {
+        let adjacency = adjacency(&[("A:1", "A:2"), ("A:2", "A:3"), ("A:3", "A:4")]);
+        let seed_nodes = vec![adjacency.node_index["A:1"]];
+
+        let (visited, _, hop_reports) = crate::expand_neighborhood(&adjacency, &seed_nodes, 2, &None, None, &mut StdRng::seed_from_u64(1));
+        assert_eq!(node_ids(&adjacency, &visited), vec!["A:1", "A:2", "A:3"]);
+        assert_eq!(hop_reports.len(), 2);
+        assert_eq!(hop_reports[1].nodes_added, 1);
+    }
+
+    #[test]
+    fn test_fan_out_cap() {
+        let adjacency = adjacency(&[("A:0", "A:1"), ("A:0", "A:2"), ("A:0", "A:3"), ("A:0", "A:4"), ("A:0", "A:5")]);
+        let seed_nodes = vec![adjacency.node_index["A:0"]];
+
+        let expand = |seed: u64| crate::expand_neighborhood(&adjacency, &seed_nodes, 1, &None, Some(2), &mut StdRng::seed_from_u64(seed));
+        let (visited, edges, hop_reports) = expand(7);
+        assert_eq!(visited.len(), 3);
+        assert_eq!(hop_reports[0].fan_out_capped, 1);
+        assert_eq!(edges.len(), 2);
+        assert_eq!(node_ids(&adjacency, &visited), node_ids(&adjacency, &expand(7).0));
+
+        // other seeds may follow other edges, but never more than the cap
+        assert!((0..20).all(|seed| expand(seed).0.len() == 3));
+    }
+
+    #[test]
+    fn test_traversed_edges() {
+        let adjacency = adjacency(&[("A:1", "A:2"), ("A:1", "A:3"), ("A:2", "A:3"), ("A:3", "A:4")]);
+        let seed_nodes = vec![adjacency.node_index["A:1"]];
+
+        // A:2 -> A:3 joins two reached nodes but is never traversed from the seed, so it is left out
+        let (_, edges, hop_reports) = crate::expand_neighborhood(&adjacency, &seed_nodes, 1, &None, None, &mut StdRng::seed_from_u64(1));
+        assert_eq!(edges.into_iter().collect_vec(), vec![0, 1]);
+        assert_eq!(hop_reports[0].edges_added, 2);
+    }
+}
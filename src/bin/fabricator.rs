@@ -1,18 +1,60 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use humantime::format_duration;
 use indexmap::IndexMap;
-use itertools::{any, join, Itertools};
+use itertools::{any, Itertools};
 use lazy_static::lazy_static;
-use log::{debug, info};
+use log::{debug, info, warn};
 use ordered_float::OrderedFloat;
 use polars::prelude::*;
+use rand::distr::weighted::WeightedIndex;
+use rand::distr::Distribution;
+use rand::rngs::StdRng;
+use rand::{seq, Rng, SeedableRng};
+use rusty_matrix_io::FileFormat;
+use serde_derive::{Deserialize, Serialize};
 use serde_yml::{to_value, Value};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::io::{BufWriter, Write};
 use std::time::Instant;
 use std::{error, path};
 
+const LOREM_WORDS: [&str; 32] = [
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "ad",
+    "minim",
+    "veniam",
+    "quis",
+    "nostrud",
+    "exercitation",
+    "ullamco",
+    "laboris",
+    "nisi",
+    "aliquip",
+    "ex",
+    "commodo",
+];
+
 lazy_static! {
     pub static ref SEPARATOR: String = format!("{}", char::from_u32(0x0000001F).unwrap()).to_string();
     pub static ref KNOWN_TYPE_VALUES: HashMap<String, Vec<String>> = HashMap::from([
@@ -65,6 +107,159 @@ lazy_static! {
     ]);
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TableSpec {
+    pub num_rows: NumRows,
+    pub columns: IndexMap<String, ColumnSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub triples: Option<Vec<TripleSpec>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TripleSpec {
+    pub subject_category: String,
+    pub predicate: String,
+    pub object_category: String,
+    pub weight: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NumRows {
+    Count(usize),
+    Reference(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SampleSpec {
+    pub num_rows: NumRows,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ColumnSpec {
+    GenerateUniqueId {
+        #[serde(default)]
+        prefixes: Vec<String>,
+        prefix: Option<String>,
+        id_length: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        by_category: Option<IndexMap<String, CategoryIdSpec>>,
+    },
+    GenerateValues {
+        sample_values: Vec<Value>,
+    },
+    CopyColumn {
+        source_column: String,
+        seed: Option<u64>,
+        sample: Option<SampleSpec>,
+    },
+    GenerateRandomNumbers {
+        #[serde(default)]
+        dtype: NumberType,
+        min: f64,
+        max: f64,
+        mean: Option<f64>,
+        std: Option<f64>,
+        quantiles: Option<Vec<f64>>,
+    },
+    GenerateRandomBooleans {
+        true_ratio: f64,
+    },
+    GenerateDates {
+        start_date: String,
+        end_date: String,
+    },
+    GenerateLoremText {
+        length_distribution: BTreeMap<usize, usize>,
+    },
+    GenerateCorrelatedValues {
+        #[serde(default = "default_correlated_by")]
+        by: String,
+        distributions: IndexMap<String, BTreeMap<String, usize>>,
+    },
+    GenerateRandomArrays {
+        sample_values: Vec<Value>,
+        #[serde(default = "default_array_delimiter")]
+        delimiter: String,
+        length_distribution: Option<BTreeMap<usize, usize>>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct CategoryIdSpec {
+    pub weight: usize,
+    pub prefixes: BTreeMap<String, usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_length: Option<usize>,
+    #[serde(default)]
+    pub zero_padded: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NumberType {
+    Int,
+    #[default]
+    Float,
+}
+
+fn default_correlated_by() -> String {
+    "predicate".to_string()
+}
+
+fn default_array_delimiter() -> String {
+    "|".to_string()
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextPolicy {
+    Keep,
+    Hash,
+    Synthesize,
+    Drop,
+}
+
+#[derive(Args, Clone, PartialEq, Debug)]
+struct ColumnMapOptions {
+    /// columns with more distinct values than this are described by a distribution instead of enumerated
+    #[arg(short = 't', long, default_value_t = 50)]
+    distinct_threshold: usize,
+
+    /// policy for columns detected as free text
+    #[arg(long, value_enum, default_value_t = TextPolicy::Synthesize)]
+    text_policy: TextPolicy,
+
+    /// per-column policy as <column>=<policy>, e.g. name=hash; overrides free-text detection
+    #[arg(long)]
+    column_policy: Vec<String>,
+
+    #[arg(long, default_value_t = 40.0)]
+    free_text_min_length: f64,
+
+    #[arg(long, default_value_t = 2.0)]
+    free_text_min_tokens: f64,
+
+    /// also emit keys that only the fabricate command understands: per-category id formats, predicate-correlated columns and triples
+    #[arg(long, default_value_t = false)]
+    extended_schema: bool,
+}
+
+impl ColumnMapOptions {
+    fn text_policy(&self, cn: &str, values: &[String]) -> Option<TextPolicy> {
+        let explicit = self.column_policy.iter().find_map(|a| match a.split_once('=') {
+            Some((column, policy)) if column == cn => Some(TextPolicy::from_str(policy, true).expect("Unknown column policy")),
+            _ => None,
+        });
+        // delimited lists such as publications or equivalent_identifiers run long without being prose
+        let free_text = || detect_array_delimiter(values).is_none() && is_free_text(values, self.free_text_min_length, self.free_text_min_tokens);
+        explicit.or_else(|| free_text().then_some(self.text_policy))
+    }
+}
+
 #[derive(Parser, PartialEq, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Options {
@@ -103,6 +298,12 @@ enum Commands {
         #[arg(short, long, default_value_t = 100)]
         rows: i32,
 
+        #[command(flatten)]
+        column_map_options: ColumnMapOptions,
+
+        #[arg(short = 'd', long)]
+        seed: Option<u64>,
+
         #[arg(short = 'o', long, required = true)]
         output: path::PathBuf,
     },
@@ -119,9 +320,28 @@ enum Commands {
         #[arg(short, long, default_value_t = 100)]
         rows: i32,
 
+        #[command(flatten)]
+        column_map_options: ColumnMapOptions,
+
+        #[arg(short = 'd', long)]
+        seed: Option<u64>,
+
         #[arg(short = 'o', long, required = true)]
         output: path::PathBuf,
     },
+    Fabricate {
+        #[arg(short = 'c', long, required = true)]
+        config: path::PathBuf,
+
+        #[arg(short = 'd', long, default_value_t = 0)]
+        seed: u64,
+
+        #[arg(short = 'o', long, required = true)]
+        output_dir: path::PathBuf,
+
+        #[arg(short = 'f', long, value_enum, default_value_t = FileFormat::Tsv)]
+        output_format: FileFormat,
+    },
 }
 
 fn main() -> Result<(), Box<dyn error::Error>> {
@@ -146,9 +366,11 @@ fn main() -> Result<(), Box<dyn error::Error>> {
             schema_snapshot,
             limit,
             rows,
+            column_map_options,
+            seed,
             output,
         }) => {
-            build_yaml_from_kg_schema_snapshot(nodes, edges, schema_snapshot, limit, rows, output)
+            build_yaml_from_kg_schema_snapshot(nodes, edges, schema_snapshot, limit, rows, column_map_options, seed, output)
                 .expect("Could not build fabricator yaml from KG schema snapshot");
         }
         Some(Commands::BuildYAMLFromKGX {
@@ -156,9 +378,19 @@ fn main() -> Result<(), Box<dyn error::Error>> {
             edges,
             limit,
             rows,
+            column_map_options,
+            seed,
             output,
         }) => {
-            build_yaml_from_kgx(nodes, edges, limit, rows, output).expect("Could not build fabricator yaml from KGX");
+            build_yaml_from_kgx(nodes, edges, limit, rows, column_map_options, seed, output).expect("Could not build fabricator yaml from KGX");
+        }
+        Some(Commands::Fabricate {
+            config,
+            seed,
+            output_dir,
+            output_format,
+        }) => {
+            fabricate(config, seed, output_dir, output_format).expect("Could not fabricate KG");
         }
         None => {}
     }
@@ -167,12 +399,468 @@ fn main() -> Result<(), Box<dyn error::Error>> {
     Ok(())
 }
 
+fn fabricate(config: &path::PathBuf, seed: &u64, output_dir: &path::PathBuf, output_format: &FileFormat) -> Result<(), Box<dyn error::Error>> {
+    let config_content = fs::read_to_string(config).expect("Could not read fabricator config");
+    let tables: IndexMap<String, TableSpec> = serde_yml::from_str(&config_content)?;
+
+    let tables_df = fabricate_tables(&tables, *seed)?;
+
+    fs::create_dir_all(output_dir).expect("Could not create output directory");
+    for (table_name, mut df) in tables_df.into_iter() {
+        info!("{}: {:?}", table_name, df.shape());
+        let output = output_dir.join(format!("{}.{}", table_name, output_format.extension()));
+        rusty_matrix_io::write_dataframe(&mut df, &output, *output_format);
+    }
+    Ok(())
+}
+
+fn fabricate_tables(tables: &IndexMap<String, TableSpec>, seed: u64) -> Result<IndexMap<String, DataFrame>, Box<dyn error::Error>> {
+    let mut tables_df: IndexMap<String, DataFrame> = IndexMap::new();
+
+    // tables are generated in config order, except that a table is deferred until the tables it copies from exist
+    while tables_df.len() < tables.len() {
+        let ready = tables.iter().filter(|(table_name, _)| !tables_df.contains_key(*table_name)).find(|(_, table)| {
+            let triples_ready = table.triples.is_none() || tables_df.contains_key(triples_source(table).0.as_str());
+            triples_ready
+                && table.columns.values().all(|column| match column {
+                    ColumnSpec::CopyColumn { source_column, .. } => source_column
+                        .split_once('.')
+                        .is_some_and(|(source_table, _)| tables_df.contains_key(source_table)),
+                    _ => true,
+                })
+        });
+
+        let Some((table_name, table)) = ready else {
+            let pending = tables.keys().filter(|k| !tables_df.contains_key(*k)).collect_vec();
+            return Err(format!("Could not resolve copy_column sources for tables: {:?}", pending).into());
+        };
+
+        let num_rows = resolve_num_rows(tables, &table.num_rows, &mut HashSet::new())?;
+
+        // columns that depend on each other are drawn up front, and the columns they name are taken from here
+        let mut preassigned: HashMap<String, Vec<String>> = HashMap::new();
+
+        // per-category ids need the row's category first, and the category column then has to agree with it
+        if let Some(ColumnSpec::GenerateUniqueId {
+            by_category: Some(by_category),
+            ..
+        }) = table.columns.get("id")
+        {
+            if !by_category.is_empty() {
+                let mut rng = StdRng::seed_from_u64(column_seed(seed, None, format!("{}.category", table_name).as_str()));
+                let weights = WeightedIndex::new(by_category.values().map(|a| a.weight))?;
+                let row_categories = (0..num_rows)
+                    .map(|_| by_category.get_index(weights.sample(&mut rng)).unwrap().0.clone())
+                    .collect_vec();
+                preassigned.insert("category".to_string(), row_categories);
+            }
+        }
+
+        if let Some(triples) = table.triples.as_ref().filter(|a| !a.is_empty()) {
+            let (source_table, source_column) = triples_source(table);
+            let mut rng = StdRng::seed_from_u64(column_seed(seed, None, format!("{}.triples", table_name).as_str()));
+            preassigned.extend(assign_triples(
+                triples,
+                tables_df.get(&source_table).unwrap(),
+                &source_column,
+                num_rows,
+                &mut rng,
+            )?);
+        }
+
+        let mut columns = vec![];
+        for (column_name, column) in table.columns.iter() {
+            let copy_column_seed = match column {
+                ColumnSpec::CopyColumn { seed, .. } => *seed,
+                _ => None,
+            };
+            let mut rng = StdRng::seed_from_u64(column_seed(seed, copy_column_seed, format!("{}.{}", table_name, column_name).as_str()));
+            columns.push(match preassigned.get(column_name) {
+                Some(values) => Series::new(column_name.into(), values.clone()).into_column(),
+                None => fabricate_column(tables, &tables_df, column_name, column, num_rows, &preassigned, &mut rng)?,
+            });
+        }
+        for column_name in ["category", "subject", "predicate", "object"] {
+            if let (Some(values), false) = (preassigned.get(column_name), table.columns.contains_key(column_name)) {
+                columns.push(Series::new(column_name.into(), values.clone()).into_column());
+            }
+        }
+        tables_df.insert(table_name.clone(), DataFrame::new(columns)?);
+    }
+
+    // keep the output in config order
+    Ok(tables.keys().map(|k| (k.clone(), tables_df.swap_remove(k).unwrap())).collect())
+}
+
+fn resolve_num_rows(tables: &IndexMap<String, TableSpec>, num_rows: &NumRows, seen: &mut HashSet<String>) -> Result<usize, Box<dyn error::Error>> {
+    match num_rows {
+        NumRows::Count(count) => Ok(*count),
+        NumRows::Reference(reference) => {
+            let table_name = reference
+                .strip_prefix('@')
+                .and_then(|a| a.strip_suffix(".num_rows"))
+                .ok_or(format!("num_rows reference must look like @<table>.num_rows: {}", reference))?;
+            if !seen.insert(table_name.to_string()) {
+                return Err(format!("Circular num_rows reference: {}", reference).into());
+            }
+            let table = tables.get(table_name).ok_or(format!("Unknown table in num_rows reference: {}", reference))?;
+            resolve_num_rows(tables, &table.num_rows, seen)
+        }
+    }
+}
+
+fn column_seed(seed: u64, column_seed: Option<u64>, qualified_name: &str) -> u64 {
+    // FNV-1a over the column name, so every column draws from its own stream and adding a column doesn't shift the others
+    let name_hash = qualified_name
+        .bytes()
+        .fold(0xcbf29ce484222325_u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    seed ^ name_hash ^ column_seed.unwrap_or(0).rotate_left(32)
+}
+
+fn fabricate_column(
+    tables: &IndexMap<String, TableSpec>,
+    tables_df: &IndexMap<String, DataFrame>,
+    column_name: &str,
+    column: &ColumnSpec,
+    num_rows: usize,
+    preassigned: &HashMap<String, Vec<String>>,
+    rng: &mut StdRng,
+) -> Result<polars::prelude::Column, Box<dyn error::Error>> {
+    let series = match column {
+        ColumnSpec::GenerateUniqueId {
+            by_category: Some(by_category),
+            ..
+        } if preassigned.contains_key("category") => {
+            // numbering is per prefix, so categories sharing a prefix never collide
+            let mut next_local_id: HashMap<String, usize> = HashMap::new();
+            let mut values = vec![];
+            for category in preassigned.get("category").unwrap().iter() {
+                let category_spec = by_category.get(category).unwrap();
+                let prefix_weights = WeightedIndex::new(category_spec.prefixes.values())?;
+                let prefix = category_spec.prefixes.keys().nth(prefix_weights.sample(rng)).unwrap();
+                let local_prefix = category_spec.local_prefix.clone().unwrap_or_default();
+                let counter = next_local_id.entry(format!("{}:{}", prefix, local_prefix)).or_insert(0);
+                *counter += 1;
+                values.push(format_curie(
+                    prefix,
+                    &local_prefix,
+                    *counter,
+                    category_spec.id_length,
+                    category_spec.zero_padded,
+                ));
+            }
+            Series::new(column_name.into(), values)
+        }
+        ColumnSpec::GenerateUniqueId {
+            prefixes, prefix, id_length, ..
+        } => {
+            let prefixes = prefix.iter().chain(prefixes.iter()).map(|a| a.trim_end_matches(':').to_string()).collect_vec();
+            let width = id_length.unwrap_or(0);
+            let values = (1..=num_rows)
+                .map(|idx| match prefixes.is_empty() {
+                    true => format!("{:0width$}", idx, width = width),
+                    false => format!("{}:{:0width$}", prefixes[rng.random_range(0..prefixes.len())], idx, width = width),
+                })
+                .collect_vec();
+            Series::new(column_name.into(), values)
+        }
+        ColumnSpec::GenerateValues { sample_values } => {
+            if sample_values.is_empty() {
+                return Err(format!("generate_values for {} has no sample_values", column_name).into());
+            }
+            let values = (0..num_rows)
+                .map(|_| sample_values[rng.random_range(0..sample_values.len())].clone())
+                .collect_vec();
+            series_from_values(column_name, values)
+        }
+        ColumnSpec::CopyColumn { source_column, sample, .. } => {
+            let (source_table, source_column_name) = source_column
+                .split_once('.')
+                .ok_or(format!("source_column must look like <table>.<column>: {}", source_column))?;
+            let source = tables_df
+                .get(source_table)
+                .unwrap()
+                .column(source_column_name)?
+                .as_materialized_series()
+                .clone();
+            match sample {
+                Some(sample) => {
+                    let sample_rows = resolve_num_rows(tables, &sample.num_rows, &mut HashSet::new())?;
+                    if source.is_empty() {
+                        return Err(format!("Cannot sample {} rows from empty column {}", sample_rows, source_column).into());
+                    }
+                    let indexes = (0..sample_rows).map(|_| rng.random_range(0..source.len()) as IdxSize).collect_vec();
+                    source.take(&IdxCa::from_vec("idx".into(), indexes))?.with_name(column_name.into())
+                }
+                None => source.with_name(column_name.into()),
+            }
+        }
+        ColumnSpec::GenerateRandomNumbers {
+            dtype,
+            min,
+            max,
+            mean,
+            std,
+            quantiles,
+        } => {
+            let values = (0..num_rows)
+                .map(|_| random_number(rng, *min, *max, *mean, *std, quantiles.as_deref()))
+                .collect_vec();
+            match dtype {
+                NumberType::Int => Series::new(column_name.into(), values.iter().map(|a| a.round() as i64).collect_vec()),
+                NumberType::Float => Series::new(column_name.into(), values),
+            }
+        }
+        ColumnSpec::GenerateRandomBooleans { true_ratio } => {
+            let true_ratio = true_ratio.clamp(0.0, 1.0);
+            Series::new(column_name.into(), (0..num_rows).map(|_| rng.random_bool(true_ratio)).collect_vec())
+        }
+        ColumnSpec::GenerateDates { start_date, end_date } => {
+            let bounds = Series::new("bounds".into(), [start_date.as_str(), end_date.as_str()])
+                .cast(&DataType::Date)?
+                .cast(&DataType::Int32)?;
+            let (Some(start), Some(end)) = (bounds.i32()?.get(0), bounds.i32()?.get(1)) else {
+                return Err(format!("generate_dates for {} needs YYYY-MM-DD dates: {} to {}", column_name, start_date, end_date).into());
+            };
+            let days = (0..num_rows).map(|_| rng.random_range(start.min(end)..=end.max(start))).collect_vec();
+            Series::new(column_name.into(), days).cast(&DataType::Date)?
+        }
+        ColumnSpec::GenerateLoremText { length_distribution } => {
+            let lengths = length_distribution.keys().collect_vec();
+            let length_weights =
+                WeightedIndex::new(length_distribution.values()).map_err(|e| format!("Invalid length_distribution for {}: {}", column_name, e))?;
+            let values = (0..num_rows)
+                .map(|_| {
+                    let length = *lengths[length_weights.sample(rng)];
+                    (0..length).map(|_| LOREM_WORDS[rng.random_range(0..LOREM_WORDS.len())]).join(" ")
+                })
+                .collect_vec();
+            Series::new(column_name.into(), values)
+        }
+        ColumnSpec::GenerateCorrelatedValues { by, distributions } => {
+            let by_values = preassigned
+                .get(by)
+                .ok_or(format!("generate_correlated_values for {} needs a generated {} column", column_name, by))?;
+            let weights = distributions
+                .iter()
+                .map(|(k, v)| WeightedIndex::new(v.values()).map(|w| (k.clone(), w)))
+                .collect::<Result<HashMap<_, _>, _>>()?;
+            let values = by_values
+                .iter()
+                .map(|by_value| match (distributions.get(by_value), weights.get(by_value)) {
+                    (Some(distribution), Some(weights)) => Some(distribution.keys().nth(weights.sample(rng)).unwrap().clone()),
+                    _ => None,
+                })
+                .collect_vec();
+            Series::new(column_name.into(), values)
+        }
+        ColumnSpec::GenerateRandomArrays {
+            sample_values,
+            delimiter,
+            length_distribution,
+        } => {
+            let pool = sample_values.iter().map(value_to_string).collect_vec();
+            // array lengths follow the observed distribution when the config has one, otherwise any length up to the pool size
+            let lengths = match length_distribution {
+                Some(length_distribution) if !length_distribution.is_empty() => length_distribution.clone(),
+                _ => (1..=pool.len()).map(|length| (length, 1)).collect(),
+            };
+            let length_weights = WeightedIndex::new(lengths.values()).map_err(|e| format!("Invalid length_distribution for {}: {}", column_name, e));
+            let values = (0..num_rows)
+                .map(|_| match (pool.is_empty(), &length_weights) {
+                    (true, _) | (_, Err(_)) => "".to_string(),
+                    (false, Ok(length_weights)) => {
+                        let length = (*lengths.keys().nth(length_weights.sample(rng)).unwrap()).min(pool.len());
+                        seq::index::sample(rng, pool.len(), length)
+                            .into_iter()
+                            .sorted()
+                            .map(|i| pool[i].clone())
+                            .join(delimiter)
+                    }
+                })
+                .collect_vec();
+            Series::new(column_name.into(), values)
+        }
+    };
+
+    if series.len() != num_rows {
+        warn!("{} has {} rows, expected {}", column_name, series.len(), num_rows);
+    }
+    Ok(series.into_column())
+}
+
+fn triples_source(table: &TableSpec) -> (String, String) {
+    match table.columns.get("subject") {
+        Some(ColumnSpec::CopyColumn { source_column, .. }) => match source_column.split_once('.') {
+            Some((source_table, source_column)) => (source_table.to_string(), source_column.to_string()),
+            None => ("nodes".to_string(), "id".to_string()),
+        },
+        _ => ("nodes".to_string(), "id".to_string()),
+    }
+}
+
+fn assign_triples(
+    triples: &[TripleSpec],
+    nodes_df: &DataFrame,
+    id_column: &str,
+    num_rows: usize,
+    rng: &mut StdRng,
+) -> Result<HashMap<String, Vec<String>>, Box<dyn error::Error>> {
+    let ids = nodes_df.column(id_column)?.cast(&DataType::String)?;
+    let categories = nodes_df.column("category")?.cast(&DataType::String)?;
+    let mut ids_by_category: HashMap<&str, Vec<&str>> = HashMap::new();
+    ids.str()?.into_iter().zip(categories.str()?).for_each(|(id, category)| {
+        if let (Some(id), Some(category)) = (id, category) {
+            ids_by_category.entry(category).or_default().push(id);
+        }
+    });
+
+    // a triple whose categories were not fabricated can't be realized, so it is left out of the draw
+    let usable = triples
+        .iter()
+        .filter(|a| ids_by_category.contains_key(a.subject_category.as_str()) && ids_by_category.contains_key(a.object_category.as_str()))
+        .collect_vec();
+    if usable.len() < triples.len() {
+        warn!(
+            "{} of {} triples have no fabricated nodes for their categories",
+            triples.len() - usable.len(),
+            triples.len()
+        );
+    }
+    if usable.is_empty() {
+        return Err("None of the triples can be realized with the fabricated nodes".into());
+    }
+    let weights = WeightedIndex::new(usable.iter().map(|a| a.weight))?;
+
+    let mut assigned: HashMap<String, Vec<String>> = HashMap::new();
+    for _ in 0..num_rows {
+        let triple = usable[weights.sample(rng)];
+        let subjects = ids_by_category.get(triple.subject_category.as_str()).unwrap();
+        let objects = ids_by_category.get(triple.object_category.as_str()).unwrap();
+        assigned
+            .entry("subject".to_string())
+            .or_default()
+            .push(subjects[rng.random_range(0..subjects.len())].to_string());
+        assigned.entry("predicate".to_string()).or_default().push(triple.predicate.clone());
+        assigned
+            .entry("object".to_string())
+            .or_default()
+            .push(objects[rng.random_range(0..objects.len())].to_string());
+    }
+    Ok(assigned)
+}
+
+fn format_curie(prefix: &str, local_prefix: &str, number: usize, id_length: Option<usize>, zero_padded: bool) -> String {
+    match (id_length, zero_padded) {
+        (Some(id_length), true) => format!("{}:{}{:0width$}", prefix, local_prefix, number, width = id_length),
+        // unpadded ids keep the observed digit count by counting up from the smallest number of that length
+        (Some(id_length), false) => format!("{}:{}{}", prefix, local_prefix, 10_usize.pow(id_length.saturating_sub(1) as u32) + number - 1),
+        (None, _) => format!("{}:{}{}", prefix, local_prefix, number),
+    }
+}
+
+fn learn_category_id_formats(ids: &[String], categories: &[String]) -> IndexMap<String, CategoryIdSpec> {
+    let mut local_parts_by_category: IndexMap<String, Vec<(String, String)>> = IndexMap::new();
+    ids.iter().zip(categories.iter()).for_each(|(id, category)| {
+        let (prefix, local_part) = id.split_once(':').unwrap_or(("", id.as_str()));
+        local_parts_by_category
+            .entry(category.clone())
+            .or_default()
+            .push((prefix.to_string(), local_part.to_string()));
+    });
+    local_parts_by_category.sort_keys();
+
+    local_parts_by_category
+        .into_iter()
+        .map(|(category, local_parts)| {
+            let prefixes: BTreeMap<String, usize> = local_parts.iter().map(|(prefix, _)| prefix.clone()).counts().into_iter().collect();
+
+            // the local prefix is the non-digit lead every local part shares, e.g. CHEMBL in CHEMBL.COMPOUND:CHEMBL25
+            let leads = local_parts
+                .iter()
+                .map(|(_, local_part)| local_part.chars().take_while(|c| !c.is_ascii_digit()).collect::<String>())
+                .collect_vec();
+            let local_prefix = leads.iter().all_equal_value().ok().filter(|a| !a.is_empty()).cloned();
+            let digits = local_parts
+                .iter()
+                .map(|(_, local_part)| &local_part[local_prefix.as_ref().map(|a| a.len()).unwrap_or(0).min(local_part.len())..])
+                .filter(|a| !a.is_empty() && a.chars().all(|c| c.is_ascii_digit()))
+                .collect_vec();
+
+            let id_length = digits
+                .iter()
+                .map(|a| a.len())
+                .counts()
+                .into_iter()
+                .max_by_key(|(length, count)| (*count, *length))
+                .map(|(length, _)| length);
+            let zero_padded = digits.iter().any(|a| a.len() > 1 && a.starts_with('0'));
+
+            let spec = CategoryIdSpec {
+                weight: local_parts.len(),
+                prefixes,
+                local_prefix,
+                id_length,
+                zero_padded,
+            };
+            (category, spec)
+        })
+        .collect()
+}
+
+fn random_number(rng: &mut StdRng, min: f64, max: f64, mean: Option<f64>, std: Option<f64>, quantiles: Option<&[f64]>) -> f64 {
+    let (low, high) = (min.min(max), max.max(min));
+    let value = match (quantiles, mean, std) {
+        // evenly spaced quantiles describe the inverse CDF piecewise linearly
+        (Some(quantiles), _, _) if quantiles.len() > 1 => {
+            let position = rng.random_range(0.0..=1.0) * (quantiles.len() - 1) as f64;
+            let lower = (position.floor() as usize).min(quantiles.len() - 2);
+            quantiles[lower] + (quantiles[lower + 1] - quantiles[lower]) * (position - lower as f64)
+        }
+        (_, Some(mean), Some(std)) => {
+            // Box-Muller
+            let u1: f64 = rng.random_range(f64::EPSILON..1.0);
+            let u2: f64 = rng.random_range(0.0..1.0);
+            mean + std * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+        }
+        _ => match low < high {
+            true => rng.random_range(low..=high),
+            false => low,
+        },
+    };
+    value.clamp(low, high)
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "".to_string(),
+        other => serde_yml::to_string(other).unwrap().trim().to_string(),
+    }
+}
+
+fn series_from_values(column_name: &str, values: Vec<Value>) -> Series {
+    if values.iter().all(|a| a.is_i64()) {
+        return Series::new(column_name.into(), values.iter().map(|a| a.as_i64()).collect_vec());
+    }
+    if values.iter().all(|a| a.is_number()) {
+        return Series::new(column_name.into(), values.iter().map(|a| a.as_f64()).collect_vec());
+    }
+    if values.iter().all(|a| a.is_bool()) {
+        return Series::new(column_name.into(), values.iter().map(|a| a.as_bool()).collect_vec());
+    }
+    Series::new(column_name.into(), values.iter().map(value_to_string).collect_vec())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn build_yaml_from_kg_schema_snapshot(
     nodes: &path::PathBuf,
     edges: &path::PathBuf,
     schema_snapshot: &path::PathBuf,
     limit: &i32,
     rows: &i32,
+    column_map_options: &ColumnMapOptions,
+    seed: &Option<u64>,
     output: &path::PathBuf,
 ) -> Result<(), Box<dyn error::Error>> {
     let schema_snapshot_content = fs::read_to_string(schema_snapshot).expect("Could not read schema snapshot");
@@ -183,59 +871,26 @@ fn build_yaml_from_kg_schema_snapshot(
     let size: f32 = *limit as f32 / 2.0;
     let size: IdxSize = size.round() as IdxSize;
 
-    let edges_df = LazyCsvReader::new(edges)
-        .with_separator(b'\t')
-        .with_truncate_ragged_lines(true)
-        .with_has_header(true)
-        .with_ignore_errors(true)
-        .finish()
-        .unwrap()
-        .select(usable_edges_columns)
-        .limit(size)
-        .collect()
-        .unwrap();
-
-    let edge_id_columns_df = edges_df
-        .clone()
-        .lazy()
-        .select([col("subject"), col("object")])
-        .collect()
-        .expect("Could not get the subject or object columns");
-
-    let edge_ids_df = concat(
-        [
-            edge_id_columns_df.clone().lazy().select([col("subject").alias("id")]),
-            edge_id_columns_df.clone().lazy().select([col("object").alias("id")]),
-        ],
-        UnionArgs::default(),
-    )
-    .unwrap()
-    .unique(None, UniqueKeepStrategy::First)
-    .collect()
-    .unwrap();
+    let edges_lf = rusty_matrix_io::scan_kg_file(edges);
 
-    let selected_edge_ids: Vec<Option<&str>> = edge_ids_df.column("id").unwrap().as_series().unwrap().str().unwrap().into_iter().collect();
-
-    let re = format!("^({})$", join(selected_edge_ids.into_iter().filter_map(|a| a.map(|a| a.to_string())), "|"));
+    let edges_df = sample_edges(edges_lf, size as usize, seed).select(usable_edges_columns).collect().unwrap();
 
     let usable_nodes_columns = snapshot.nodes.iter().map(|a| col(a.name.clone())).collect_vec();
-    let nodes_df = LazyCsvReader::new(nodes)
-        .with_separator(b'\t')
-        .with_truncate_ragged_lines(true)
-        .with_has_header(true)
-        .with_ignore_errors(true)
-        .finish()
-        .unwrap()
-        .select(usable_nodes_columns)
-        .filter(col("id").str().contains(lit(re), false))
-        .collect()
-        .unwrap();
+    let nodes_lf = rusty_matrix_io::scan_kg_file(nodes);
+
+    let nodes_df = select_nodes(nodes_lf, &edges_df).select(usable_nodes_columns).collect().unwrap();
 
     debug!("nodes_df.shape(): {:?}", nodes_df.shape());
 
     let mut map = IndexMap::new();
-    map.insert("nodes".to_string(), create_nodes_map(&nodes_df, rows).expect("Could not create nodes map"));
-    map.insert("edges".to_string(), create_edges_map(&edges_df, rows).expect("Could not create edges map"));
+    map.insert(
+        "nodes".to_string(),
+        create_nodes_map(&nodes_df, rows, column_map_options).expect("Could not create nodes map"),
+    );
+    map.insert(
+        "edges".to_string(),
+        create_edges_map(&edges_df, &nodes_df, rows, column_map_options).expect("Could not create edges map"),
+    );
 
     let yaml = serde_yml::to_string(&map).unwrap();
     let output_file = fs::File::create(output).expect("Could not create output file");
@@ -244,56 +899,38 @@ fn build_yaml_from_kg_schema_snapshot(
     Ok(())
 }
 
-fn build_yaml_from_kgx(nodes: &path::PathBuf, edges: &path::PathBuf, limit: &i32, rows: &i32, output: &path::PathBuf) -> Result<(), Box<dyn error::Error>> {
+fn build_yaml_from_kgx(
+    nodes: &path::PathBuf,
+    edges: &path::PathBuf,
+    limit: &i32,
+    rows: &i32,
+    column_map_options: &ColumnMapOptions,
+    seed: &Option<u64>,
+    output: &path::PathBuf,
+) -> Result<(), Box<dyn error::Error>> {
     let size: f32 = *limit as f32 / 2.0;
     let size: IdxSize = size.round() as IdxSize;
 
-    let edges_df = LazyCsvReader::new(edges)
-        .with_separator(b'\t')
-        .with_truncate_ragged_lines(true)
-        .with_has_header(true)
-        .with_ignore_errors(true)
-        .finish()
-        .unwrap()
-        .limit(size)
-        .collect()
-        .unwrap();
+    let edges_lf = rusty_matrix_io::scan_kg_file(edges);
 
-    let edge_id_columns_df = edges_df.clone().lazy().select([col("subject"), col("object")]).collect().unwrap();
+    let edges_df = sample_edges(edges_lf, size as usize, seed).collect().unwrap();
 
-    let edge_ids_df = concat(
-        [
-            edge_id_columns_df.clone().lazy().select([col("subject").alias("id")]),
-            edge_id_columns_df.clone().lazy().select([col("object").alias("id")]),
-        ],
-        UnionArgs::default(),
-    )
-    .unwrap()
-    .unique(None, UniqueKeepStrategy::First)
-    .collect()
-    .unwrap();
+    let nodes_lf = rusty_matrix_io::scan_kg_file(nodes);
 
-    let selected_edge_ids: Vec<Option<&str>> = edge_ids_df.column("id").unwrap().as_series().unwrap().str().unwrap().into_iter().collect();
-
-    let re = format!("^({})$", join(selected_edge_ids.into_iter().filter_map(|a| a.map(|a| a.to_string())), "|"));
-
-    let nodes_df = LazyCsvReader::new(nodes)
-        .with_separator(b'\t')
-        .with_truncate_ragged_lines(true)
-        .with_has_header(true)
-        .with_ignore_errors(true)
-        .finish()
-        .unwrap()
-        .filter(col("id").str().contains(lit(re), false))
-        .collect()
-        .unwrap();
+    let nodes_df = select_nodes(nodes_lf, &edges_df).collect().unwrap();
 
     debug!("nodes_df.shape(): {:?}", nodes_df.shape());
 
     let mut map = IndexMap::new();
 
-    map.insert("nodes".to_string(), create_nodes_map(&nodes_df, rows).expect("Could not create nodes map"));
-    map.insert("edges".to_string(), create_edges_map(&edges_df, rows).expect("Could not create edges map"));
+    map.insert(
+        "nodes".to_string(),
+        create_nodes_map(&nodes_df, rows, column_map_options).expect("Could not create nodes map"),
+    );
+    map.insert(
+        "edges".to_string(),
+        create_edges_map(&edges_df, &nodes_df, rows, column_map_options).expect("Could not create edges map"),
+    );
 
     let yaml = serde_yml::to_string(&map).unwrap();
     let output_file = fs::File::create(output).expect("Could not create output file");
@@ -302,22 +939,43 @@ fn build_yaml_from_kgx(nodes: &path::PathBuf, edges: &path::PathBuf, limit: &i32
     Ok(())
 }
 
+fn sample_edges(edges_lf: LazyFrame, size: usize, seed: &Option<u64>) -> LazyFrame {
+    let seed = seed.unwrap_or_else(rand::random);
+    info!("sampling {} edges stratified by predicate with seed: {}", size, seed);
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    // only the predicate column is held in memory to pick rows; the full rows are gathered by row index afterward
+    let edges_lf = edges_lf.with_row_index("_row_index", None);
+    let predicates_df = edges_lf.clone().select([col("_row_index"), col("predicate")]).collect().unwrap();
+    let sampled_df = rusty_matrix_io::sample_stratified(&predicates_df, "predicate", size, 1, &BTreeMap::new(), &mut rng);
+
+    edges_lf
+        .semi_join(sampled_df.lazy().select([col("_row_index")]), col("_row_index"), col("_row_index"))
+        .drop(["_row_index"])
+}
+
+fn select_nodes(nodes_lf: LazyFrame, edges_df: &DataFrame) -> LazyFrame {
+    let edge_ids_lf = concat(
+        [
+            edges_df.clone().lazy().select([col("subject").alias("id")]),
+            edges_df.clone().lazy().select([col("object").alias("id")]),
+        ],
+        UnionArgs::default(),
+    )
+    .unwrap();
+
+    nodes_lf
+        .semi_join(edge_ids_lf, col("id"), col("id"))
+        .unique_stable(Some(vec!["id".into()]), UniqueKeepStrategy::First)
+}
+
 fn create_kg_schema_snapshot(
     nodes: &path::PathBuf,
     edges: &path::PathBuf,
     nodes_prefix_exclusions: &Option<Vec<String>>,
     edges_prefix_exclusions: &Option<Vec<String>>,
 ) -> Result<(), Box<dyn error::Error>> {
-    let edges_df = LazyCsvReader::new(edges)
-        .with_separator(b'\t')
-        .with_truncate_ragged_lines(true)
-        .with_has_header(true)
-        .with_ignore_errors(true)
-        .finish()
-        .unwrap()
-        .limit(10)
-        .collect()
-        .unwrap();
+    let edges_df = rusty_matrix_io::scan_kg_file(edges).limit(10).collect().unwrap();
 
     let mut edges_columns = vec![];
 
@@ -333,16 +991,13 @@ fn create_kg_schema_snapshot(
 
     for cn in edges_column_names.iter() {
         let c = edges_df.column(cn).unwrap();
-        let sample = c
-            .filter(&c.is_not_null())
-            .unwrap()
-            .cast(&DataType::String)
-            .unwrap()
-            .sample_n(6, true, true, None)
-            .unwrap();
+        let sample = c.filter(&c.is_not_null()).unwrap().sample_n(6, true, true, None).unwrap();
 
-        let cn_values_as_vec: Vec<Option<&str>> = sample.str().unwrap().into_iter().collect();
-        let cn_values_as_vec = cn_values_as_vec.into_iter().filter_map(|a| a.map(|s| s.to_string())).dedup().collect_vec();
+        let cn_values_as_vec = column_values_as_strings(sample.as_materialized_series())
+            .into_iter()
+            .flatten()
+            .dedup()
+            .collect_vec();
 
         edges_columns.push(rusty_matrix_io::Column {
             name: cn.parse().unwrap(),
@@ -353,16 +1008,7 @@ fn create_kg_schema_snapshot(
 
     let mut nodes_columns = vec![];
 
-    let nodes_df = LazyCsvReader::new(nodes)
-        .with_separator(b'\t')
-        .with_truncate_ragged_lines(true)
-        .with_has_header(true)
-        .with_ignore_errors(true)
-        .finish()
-        .unwrap()
-        .limit(10)
-        .collect()
-        .unwrap();
+    let nodes_df = rusty_matrix_io::scan_kg_file(nodes).limit(10).collect().unwrap();
 
     let nodes_column_names = match nodes_prefix_exclusions {
         Some(prefixes) => nodes_df
@@ -376,16 +1022,13 @@ fn create_kg_schema_snapshot(
 
     for cn in nodes_column_names.iter() {
         let c = nodes_df.column(cn).unwrap();
-        let sample = c
-            .filter(&c.is_not_null())
-            .unwrap()
-            .cast(&DataType::String)
-            .unwrap()
-            .sample_n(6, true, true, None)
-            .unwrap();
+        let sample = c.filter(&c.is_not_null()).unwrap().sample_n(6, true, true, None).unwrap();
 
-        let cn_values_as_vec: Vec<Option<&str>> = sample.str().unwrap().into_iter().collect();
-        let cn_values_as_vec = cn_values_as_vec.into_iter().filter_map(|a| a.map(|s| s.to_string())).dedup().collect_vec();
+        let cn_values_as_vec = column_values_as_strings(sample.as_materialized_series())
+            .into_iter()
+            .flatten()
+            .dedup()
+            .collect_vec();
 
         nodes_columns.push(rusty_matrix_io::Column {
             name: cn.parse().unwrap(),
@@ -403,7 +1046,38 @@ fn create_kg_schema_snapshot(
     Ok(())
 }
 
-fn create_nodes_map(df: &DataFrame, rows: &i32) -> Result<IndexMap<String, Value>, Box<dyn error::Error>> {
+fn column_values_as_strings(series: &Series) -> Vec<Option<String>> {
+    match series.dtype() {
+        // native lists are rendered the way KGX writes them
+        DataType::List(_) => series
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|a| {
+                a.map(|inner| {
+                    inner
+                        .cast(&DataType::String)
+                        .unwrap()
+                        .str()
+                        .unwrap()
+                        .into_iter()
+                        .flatten()
+                        .join(SEPARATOR.as_str())
+                })
+            })
+            .collect_vec(),
+        _ => series
+            .cast(&DataType::String)
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .map(|a| a.map(|s| s.to_string()))
+            .collect_vec(),
+    }
+}
+
+fn create_nodes_map(df: &DataFrame, rows: &i32, column_map_options: &ColumnMapOptions) -> Result<IndexMap<String, Value>, Box<dyn error::Error>> {
     let primary_columns = df
         .get_column_names_str()
         .iter()
@@ -424,10 +1098,11 @@ fn create_nodes_map(df: &DataFrame, rows: &i32) -> Result<IndexMap<String, Value
                 if let Some(cn_series) = df.column(&cn).unwrap().as_series() {
                     let cn_values = cn_series.clone().cast(&DataType::String).unwrap();
                     let cn_values_as_vec: Vec<Option<&str>> = cn_values.str().unwrap().into_iter().collect_vec();
-                    let mut cn_values_as_vec = cn_values_as_vec
-                        .into_iter()
-                        .filter_map(|a| a.map(|s| s.to_string()))
-                        .map(|a| a.split_once(":").unwrap().0.to_string())
+                    let ids = cn_values_as_vec.into_iter().map(|a| a.unwrap_or("").to_string()).collect_vec();
+                    let mut cn_values_as_vec = ids
+                        .iter()
+                        .filter(|a| !a.is_empty())
+                        .map(|a| a.split_once(":").map(|b| b.0).unwrap_or("").to_string())
                         .sorted()
                         .dedup()
                         .collect_vec();
@@ -435,51 +1110,31 @@ fn create_nodes_map(df: &DataFrame, rows: &i32) -> Result<IndexMap<String, Value
                         cn_values_as_vec.push("".to_string());
                     }
                     debug!("column name: {}, datatype: {:?}, values: {:?}", cn, datatype, cn_values_as_vec);
-                    debug!("column name: {}, datatype: {:?}, values: {:?}", cn, datatype, cn_values_as_vec);
                     column_map.insert("type", to_value("generate_unique_id").unwrap());
                     column_map.insert("prefixes", to_value(cn_values_as_vec).unwrap());
+
+                    if let (true, Ok(category_column)) = (column_map_options.extended_schema, df.column("category")) {
+                        let categories = column_values_as_strings(category_column.as_materialized_series())
+                            .into_iter()
+                            .map(|a| a.unwrap_or_default())
+                            .collect_vec();
+                        let by_category = learn_category_id_formats(&ids, &categories);
+                        debug!("column name: {}, by category: {:?}", cn, by_category);
+                        column_map.insert("by_category", to_value(by_category).unwrap());
+                    }
                 }
             }
             _ => {
                 if let Some(cn_series) = df.column(&cn).unwrap().as_series() {
-                    match cn_series.dtype() {
-                        DataType::Float64 => {
-                            let cn_values = cn_series.clone();
-                            let cn_values_as_vec: Vec<Option<f64>> = cn_values.f64().unwrap().into_iter().collect();
-                            let cn_values_as_vec = cn_values_as_vec
-                                .into_iter()
-                                .filter_map(|a| a)
-                                .map(|a| OrderedFloat::from(a))
-                                .sorted()
-                                .dedup()
-                                .map(|a| a.into_inner())
-                                .collect_vec();
-                            debug!("column name: {}, datatype: {:?}, values: {:?}", cn, datatype, cn_values_as_vec);
-                            column_map.insert("type", to_value("generate_values").unwrap());
-                            column_map.insert("sample_values", to_value(cn_values_as_vec).unwrap());
-                        }
-                        _ => {
-                            let cn_values = cn_series.clone().cast(&DataType::String).unwrap();
-                            let cn_values_as_vec: Vec<Option<&str>> = cn_values.str().unwrap().into_iter().collect();
-                            let mut cn_values_as_vec = cn_values_as_vec
-                                .into_iter()
-                                .filter_map(|a| a.map(|s| s.to_string()))
-                                .sorted()
-                                .dedup()
-                                .collect_vec();
-                            if cn_values_as_vec.is_empty() {
-                                cn_values_as_vec.push("".to_string());
-                            }
-                            debug!("column name: {}, datatype: {:?}, values: {:?}", cn, datatype, cn_values_as_vec);
-                            column_map.insert("type", to_value("generate_values").unwrap());
-                            column_map.insert("sample_values", to_value(cn_values_as_vec).unwrap());
-                        }
-                    }
+                    column_map = create_values_column_map(&cn, cn_series, column_map_options);
                 }
             }
         }
 
-        columns_map.insert(cn.clone(), to_value(column_map).unwrap());
+        // an empty map means the column was dropped by its text policy
+        if !column_map.is_empty() {
+            columns_map.insert(cn.clone(), to_value(column_map).unwrap());
+        }
     }
 
     let mut map = IndexMap::new();
@@ -489,7 +1144,234 @@ fn create_nodes_map(df: &DataFrame, rows: &i32) -> Result<IndexMap<String, Value
     Ok(map)
 }
 
-fn create_edges_map(df: &DataFrame, rows: &i32) -> Result<IndexMap<String, Value>, Box<dyn error::Error>> {
+fn create_values_column_map(cn: &str, cn_series: &Series, column_map_options: &ColumnMapOptions) -> IndexMap<&'static str, Value> {
+    let distinct_threshold = column_map_options.distinct_threshold;
+    let mut column_map = IndexMap::new();
+    let cn_series = cn_series.drop_nulls();
+    match cn_series.dtype() {
+        DataType::Float32
+        | DataType::Float64
+        | DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64 => {
+            let is_integer = cn_series.dtype().is_integer();
+            let cn_values = cn_series.cast(&DataType::Float64).unwrap();
+            let cn_values_as_vec = cn_values.f64().unwrap().into_iter().flatten().map(OrderedFloat::from).sorted().collect_vec();
+            let distinct_values = cn_values_as_vec.iter().dedup().map(|a| a.into_inner()).collect_vec();
+
+            match distinct_values.len() > distinct_threshold {
+                true => {
+                    let sorted = cn_values_as_vec.iter().map(|a| a.into_inner()).collect_vec();
+                    let (mean, std) = mean_and_std(&sorted);
+                    debug!(
+                        "column name: {}, datatype: {:?}, distinct: {}, mean: {}, std: {}",
+                        cn,
+                        cn_series.dtype(),
+                        distinct_values.len(),
+                        mean,
+                        std
+                    );
+                    column_map.insert("type", to_value("generate_random_numbers").unwrap());
+                    column_map.insert("dtype", to_value(if is_integer { "int" } else { "float" }).unwrap());
+                    column_map.insert("min", to_value(sorted.first()).unwrap());
+                    column_map.insert("max", to_value(sorted.last()).unwrap());
+                    column_map.insert("mean", to_value(mean).unwrap());
+                    column_map.insert("std", to_value(std).unwrap());
+                    column_map.insert("quantiles", to_value(quantiles(&sorted, 10)).unwrap());
+                }
+                false => {
+                    debug!("column name: {}, datatype: {:?}, values: {:?}", cn, cn_series.dtype(), distinct_values);
+                    column_map.insert("type", to_value("generate_values").unwrap());
+                    match is_integer {
+                        true => column_map.insert("sample_values", to_value(distinct_values.iter().map(|a| *a as i64).collect_vec()).unwrap()),
+                        false => column_map.insert("sample_values", to_value(distinct_values).unwrap()),
+                    };
+                }
+            }
+        }
+        DataType::List(_) => {
+            let elements = cn_series
+                .list()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .map(|inner| {
+                    inner
+                        .cast(&DataType::String)
+                        .unwrap()
+                        .str()
+                        .unwrap()
+                        .into_iter()
+                        .flatten()
+                        .map(|a| a.to_string())
+                        .collect_vec()
+                })
+                .collect_vec();
+            column_map = create_array_column_map(cn, cn_series.dtype(), &elements, SEPARATOR.as_str());
+        }
+        DataType::Boolean => {
+            let true_count = cn_series.bool().unwrap().sum().unwrap_or(0) as f64;
+            let true_ratio = true_count / cn_series.len().max(1) as f64;
+            debug!("column name: {}, datatype: {:?}, true_ratio: {}", cn, cn_series.dtype(), true_ratio);
+            column_map.insert("type", to_value("generate_random_booleans").unwrap());
+            column_map.insert("true_ratio", to_value(true_ratio).unwrap());
+        }
+        _ => {
+            let cn_values = cn_series.cast(&DataType::String).unwrap();
+            let cn_values_as_vec: Vec<Option<&str>> = cn_values.str().unwrap().into_iter().collect();
+            let cn_values_as_vec = cn_values_as_vec.into_iter().filter_map(|a| a.map(|s| s.to_string())).collect_vec();
+
+            if let Some((start_date, end_date)) = detect_date_range(&cn_values_as_vec) {
+                debug!("column name: {}, datatype: {:?}, dates: {} to {}", cn, cn_series.dtype(), start_date, end_date);
+                column_map.insert("type", to_value("generate_dates").unwrap());
+                column_map.insert("start_date", to_value(start_date).unwrap());
+                column_map.insert("end_date", to_value(end_date).unwrap());
+                return column_map;
+            }
+
+            match column_map_options.text_policy(cn, &cn_values_as_vec) {
+                Some(TextPolicy::Drop) => {
+                    info!("column name: {}, dropped by text policy", cn);
+                    return column_map;
+                }
+                Some(TextPolicy::Hash) => {
+                    let hashed = cn_values_as_vec
+                        .iter()
+                        .map(|a| format!("{:x}", Sha256::digest(a.as_bytes()))[..16].to_string())
+                        .sorted()
+                        .dedup()
+                        .collect_vec();
+                    debug!("column name: {}, datatype: {:?}, hashed values: {}", cn, cn_series.dtype(), hashed.len());
+                    column_map.insert("type", to_value("generate_values").unwrap());
+                    column_map.insert("sample_values", to_value(hashed).unwrap());
+                    return column_map;
+                }
+                Some(TextPolicy::Synthesize) => {
+                    let length_distribution: BTreeMap<usize, usize> =
+                        cn_values_as_vec.iter().map(|a| a.split_whitespace().count()).counts().into_iter().collect();
+                    debug!(
+                        "column name: {}, datatype: {:?}, token counts: {:?}",
+                        cn,
+                        cn_series.dtype(),
+                        length_distribution
+                    );
+                    column_map.insert("type", to_value("generate_lorem_text").unwrap());
+                    column_map.insert("length_distribution", to_value(length_distribution).unwrap());
+                    return column_map;
+                }
+                Some(TextPolicy::Keep) | None => {}
+            }
+
+            match detect_array_delimiter(&cn_values_as_vec) {
+                Some(delimiter) => {
+                    let elements = cn_values_as_vec
+                        .iter()
+                        .map(|a| a.split(delimiter.as_str()).map(|b| b.to_string()).collect_vec())
+                        .collect_vec();
+                    column_map = create_array_column_map(cn, cn_series.dtype(), &elements, delimiter.as_str());
+                }
+                None => {
+                    let mut cn_values_as_vec = cn_values_as_vec.into_iter().sorted().dedup().collect_vec();
+                    if cn_values_as_vec.is_empty() {
+                        cn_values_as_vec.push("".to_string());
+                    }
+                    if cn_values_as_vec.len() > distinct_threshold {
+                        // strings have no distribution to fall back to, so keep an evenly spaced subset of the sorted values
+                        let step = cn_values_as_vec.len() as f64 / distinct_threshold as f64;
+                        cn_values_as_vec = (0..distinct_threshold)
+                            .map(|i| cn_values_as_vec[(i as f64 * step) as usize].clone())
+                            .collect_vec();
+                    }
+                    debug!("column name: {}, datatype: {:?}, values: {:?}", cn, cn_series.dtype(), cn_values_as_vec);
+                    column_map.insert("type", to_value("generate_values").unwrap());
+                    column_map.insert("sample_values", to_value(cn_values_as_vec).unwrap());
+                }
+            }
+        }
+    }
+    column_map
+}
+
+fn create_array_column_map(cn: &str, datatype: &DataType, elements: &[Vec<String>], delimiter: &str) -> IndexMap<&'static str, Value> {
+    let element_pool = elements.iter().flatten().cloned().sorted().dedup().collect_vec();
+    let length_distribution: BTreeMap<usize, usize> = elements.iter().map(|a| a.len()).counts().into_iter().collect();
+    debug!(
+        "column name: {}, datatype: {:?}, delimiter: {:?}, elements: {:?}, lengths: {:?}",
+        cn, datatype, delimiter, element_pool, length_distribution
+    );
+    let mut column_map = IndexMap::new();
+    column_map.insert("type", to_value("generate_random_arrays").unwrap());
+    column_map.insert("delimiter", to_value(delimiter).unwrap());
+    column_map.insert("sample_values", to_value(element_pool).unwrap());
+    column_map.insert("length_distribution", to_value(length_distribution).unwrap());
+    column_map
+}
+
+fn is_free_text(values: &[String], min_length: f64, min_tokens: f64) -> bool {
+    if values.is_empty() {
+        return false;
+    }
+    let average_length = values.iter().map(|a| a.chars().count()).sum::<usize>() as f64 / values.len() as f64;
+    let average_tokens = values.iter().map(|a| a.split_whitespace().count()).sum::<usize>() as f64 / values.len() as f64;
+    average_length >= min_length || average_tokens >= min_tokens
+}
+
+fn mean_and_std(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|a| (a - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    (mean, variance.sqrt())
+}
+
+fn quantiles(sorted: &[f64], intervals: usize) -> Vec<f64> {
+    if sorted.is_empty() {
+        return vec![];
+    }
+    (0..=intervals)
+        .map(|i| {
+            let position = i as f64 / intervals as f64 * (sorted.len() - 1) as f64;
+            let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+            sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+        })
+        .collect_vec()
+}
+
+fn detect_date_range(values: &[String]) -> Option<(String, String)> {
+    let is_date = |a: &String| {
+        let parts = a.split('-').collect_vec();
+        parts.len() == 3
+            && [4, 2, 2]
+                .iter()
+                .zip(parts.iter())
+                .all(|(len, part)| part.len() == *len && part.chars().all(|c| c.is_ascii_digit()))
+    };
+    match !values.is_empty() && values.iter().all(is_date) {
+        true => Some((values.iter().min().unwrap().clone(), values.iter().max().unwrap().clone())),
+        false => None,
+    }
+}
+
+fn detect_array_delimiter(values: &[String]) -> Option<String> {
+    // KGX writes lists with 0x1F, older exports use "|"; a lone delimiter with empty elements is not treated as a list
+    [SEPARATOR.as_str(), "|"]
+        .into_iter()
+        .find(|delimiter| values.iter().any(|a| a.contains(delimiter)) && values.iter().all(|a| a.split(delimiter).all(|element| !element.trim().is_empty())))
+        .map(|a| a.to_string())
+}
+
+fn create_edges_map(
+    df: &DataFrame,
+    nodes_df: &DataFrame,
+    rows: &i32,
+    column_map_options: &ColumnMapOptions,
+) -> Result<IndexMap<String, Value>, Box<dyn error::Error>> {
     let primary_columns = df
         .get_column_names_str()
         .iter()
@@ -499,6 +1381,9 @@ fn create_edges_map(df: &DataFrame, rows: &i32) -> Result<IndexMap<String, Value
 
     let schema = df.schema();
 
+    // predicates are only generated from triples, which need node categories; correlated columns have nothing to follow otherwise
+    let has_triples = column_map_options.extended_schema && primary_columns_has_predicate(df) && nodes_df.get_column_names_str().contains(&"category");
+
     let mut columns_map = IndexMap::new();
 
     for cn in primary_columns {
@@ -514,6 +1399,13 @@ fn create_edges_map(df: &DataFrame, rows: &i32) -> Result<IndexMap<String, Value
                 sample_map.insert("num_rows", to_value("@edges.num_rows").unwrap());
                 column_map.insert("sample", to_value(sample_map).unwrap());
             }
+            _ if has_triples && is_correlated_column(&cn) && df.column(&cn).unwrap().null_count() < df.height() => {
+                let distributions = predicate_distributions(df, &cn);
+                debug!("column name: {}, datatype: {:?}, by predicate: {:?}", cn, datatype, distributions);
+                column_map.insert("type", to_value("generate_correlated_values").unwrap());
+                column_map.insert("by", to_value("predicate").unwrap());
+                column_map.insert("distributions", to_value(distributions).unwrap());
+            }
             "agent_type" | "knowledge_level" | "object_direction_qualifier" | "subject_direction_qualifier" => {
                 let cn_values_as_vec = KNOWN_TYPE_VALUES.get(&cn.to_string()).unwrap();
                 debug!("column name: {}, datatype: {:?}, values: {:?}", cn, datatype, cn_values_as_vec);
@@ -522,59 +1414,395 @@ fn create_edges_map(df: &DataFrame, rows: &i32) -> Result<IndexMap<String, Value
             }
             _ => {
                 if let Some(cn_series) = df.column(&cn).unwrap().as_series() {
-                    match cn_series.dtype() {
-                        DataType::Float64 => {
-                            let cn_values = cn_series.clone();
-                            let cn_values_as_vec: Vec<Option<f64>> = cn_values.f64().unwrap().into_iter().collect();
-                            let cn_values_as_vec = cn_values_as_vec
-                                .into_iter()
-                                .filter_map(|a| a)
-                                .map(|a| OrderedFloat::from(a))
-                                .sorted()
-                                .dedup()
-                                .map(|a| a.into_inner())
-                                .collect_vec();
-                            debug!("column name: {}, datatype: {:?}, values: {:?}", cn, datatype, cn_values_as_vec);
-                            column_map.insert("type", to_value("generate_values").unwrap());
-                            column_map.insert("sample_values", to_value(cn_values_as_vec).unwrap());
-                        }
-                        _ => {
-                            let cn_values = cn_series.clone().cast(&DataType::String).unwrap();
-                            let cn_values_as_vec: Vec<Option<&str>> = cn_values.str().unwrap().into_iter().collect();
-                            let mut cn_values_as_vec = cn_values_as_vec
-                                .into_iter()
-                                .filter_map(|a| a.map(|s| s.to_string()))
-                                .sorted()
-                                .dedup()
-                                .collect_vec();
-                            if cn_values_as_vec.is_empty() {
-                                cn_values_as_vec.push("".to_string());
-                            }
-                            debug!("column name: {}, datatype: {:?}, values: {:?}", cn, datatype, cn_values_as_vec);
-                            column_map.insert("type", to_value("generate_values").unwrap());
-                            column_map.insert("sample_values", to_value(cn_values_as_vec).unwrap());
-                        }
-                    }
+                    column_map = create_values_column_map(&cn, cn_series, column_map_options);
                 }
             }
         }
 
-        columns_map.insert(cn.clone(), to_value(column_map).unwrap());
+        // an empty map means the column was dropped by its text policy
+        if !column_map.is_empty() {
+            columns_map.insert(cn.clone(), to_value(column_map).unwrap());
+        }
     }
 
     let mut map = IndexMap::new();
     map.insert("columns".to_string(), to_value(columns_map).unwrap());
     map.insert("num_rows".to_string(), to_value(rows).unwrap());
+    if has_triples {
+        let triples = observed_triples(df, nodes_df);
+        info!("observed triples: {}", triples.len());
+        map.insert("triples".to_string(), to_value(triples).unwrap());
+    }
 
     Ok(map)
 }
 
+fn primary_columns_has_predicate(df: &DataFrame) -> bool {
+    ["subject", "predicate", "object"].iter().all(|a| df.get_column_names_str().contains(a))
+}
+
+fn is_correlated_column(cn: &str) -> bool {
+    matches!(cn, "knowledge_level" | "agent_type" | "primary_knowledge_source" | "qualified_predicate") || cn.ends_with("_qualifier")
+}
+
+fn predicate_distributions(df: &DataFrame, cn: &str) -> IndexMap<String, BTreeMap<String, usize>> {
+    let counts_df = df
+        .clone()
+        .lazy()
+        .filter(col(cn).is_not_null())
+        .group_by([col("predicate").cast(DataType::String), col(cn).cast(DataType::String)])
+        .agg([len().alias("count")])
+        .sort(["predicate", cn], SortMultipleOptions::default())
+        .collect()
+        .unwrap();
+
+    let predicates = counts_df.column("predicate").unwrap().str().unwrap().clone();
+    let values = counts_df.column(cn).unwrap().str().unwrap().clone();
+    let counts = counts_df.column("count").unwrap().cast(&DataType::UInt64).unwrap().u64().unwrap().clone();
+
+    let mut distributions: IndexMap<String, BTreeMap<String, usize>> = IndexMap::new();
+    for ((predicate, value), count) in predicates.into_iter().zip(&values).zip(&counts) {
+        if let (Some(predicate), Some(value), Some(count)) = (predicate, value, count) {
+            distributions
+                .entry(predicate.to_string())
+                .or_default()
+                .insert(value.to_string(), count as usize);
+        }
+    }
+    distributions
+}
+
+fn observed_triples(df: &DataFrame, nodes_df: &DataFrame) -> Vec<TripleSpec> {
+    let node_categories_lf = df!(
+        "id" => column_values_as_strings(nodes_df.column("id").unwrap().as_materialized_series()),
+        "category" => column_values_as_strings(nodes_df.column("category").unwrap().as_materialized_series()),
+    )
+    .unwrap()
+    .lazy()
+    .unique_stable(Some(vec!["id".into()]), UniqueKeepStrategy::First);
+
+    let triples_df = df
+        .clone()
+        .lazy()
+        .select([
+            col("subject").cast(DataType::String),
+            col("predicate").cast(DataType::String),
+            col("object").cast(DataType::String),
+        ])
+        .join(
+            node_categories_lf
+                .clone()
+                .select([col("id").alias("subject"), col("category").alias("subject_category")]),
+            [col("subject")],
+            [col("subject")],
+            JoinArgs::new(JoinType::Inner),
+        )
+        .join(
+            node_categories_lf.select([col("id").alias("object"), col("category").alias("object_category")]),
+            [col("object")],
+            [col("object")],
+            JoinArgs::new(JoinType::Inner),
+        )
+        .filter(col("subject_category").is_not_null().and(col("object_category").is_not_null()))
+        .group_by([col("subject_category"), col("predicate"), col("object_category")])
+        .agg([len().alias("weight")])
+        .sort(["subject_category", "predicate", "object_category"], SortMultipleOptions::default())
+        .collect()
+        .unwrap();
+
+    let column_values = |cn: &str| {
+        triples_df
+            .column(cn)
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .map(|a| a.unwrap_or("").to_string())
+            .collect_vec()
+    };
+    let weights = triples_df
+        .column("weight")
+        .unwrap()
+        .cast(&DataType::UInt64)
+        .unwrap()
+        .u64()
+        .unwrap()
+        .into_iter()
+        .map(|a| a.unwrap_or(0) as usize)
+        .collect_vec();
+
+    itertools::izip!(
+        column_values("subject_category"),
+        column_values("predicate"),
+        column_values("object_category"),
+        weights
+    )
+    .map(|(subject_category, predicate, object_category, weight)| TripleSpec {
+        subject_category,
+        predicate,
+        object_category,
+        weight,
+    })
+    .collect_vec()
+}
+
 #[cfg(test)]
 mod test {
     use indexmap::IndexMap;
+    use polars::prelude::*;
     use serde_yml::to_value;
     use std::collections::BTreeMap;
 
+    #[test]
+    fn test_fabricate_tables() {
+        let yaml = r#"
+nodes:
+  columns:
+    id:
+      type: generate_unique_id
+      prefixes:
+      - MONDO
+      - CHEBI
+    category:
+      type: generate_values
+      sample_values:
+      - biolink:Disease
+      - biolink:SmallMolecule
+  num_rows: 50
+edges:
+  columns:
+    subject:
+      type: copy_column
+      source_column: nodes.id
+      seed: 590590
+      sample:
+        num_rows: '@edges.num_rows'
+    object:
+      type: copy_column
+      source_column: nodes.id
+      seed: 590590
+      sample:
+        num_rows: '@edges.num_rows'
+    score:
+      type: generate_values
+      sample_values:
+      - 0.5
+      - 1
+    p_value:
+      type: generate_random_numbers
+      min: 0.0
+      max: 0.05
+      quantiles: [0.0, 0.001, 0.01, 0.05]
+    negated:
+      type: generate_random_booleans
+      true_ratio: 0.1
+    update_date:
+      type: generate_dates
+      start_date: 2020-01-01
+      end_date: 2024-12-31
+    knowledge_level:
+      type: generate_correlated_values
+      by: predicate
+      distributions:
+        biolink:treats:
+          knowledge_assertion: 3
+          prediction: 1
+  num_rows: 100
+  triples:
+  - subject_category: biolink:SmallMolecule
+    predicate: biolink:treats
+    object_category: biolink:Disease
+    weight: 1
+"#;
+        let tables: IndexMap<String, crate::TableSpec> = serde_yml::from_str(yaml).unwrap();
+        let tables_df = crate::fabricate_tables(&tables, 42).unwrap();
+        assert_eq!(tables_df.get("nodes").unwrap().height(), 50);
+        assert_eq!(tables_df.get("edges").unwrap().height(), 100);
+        assert_eq!(tables_df.get("nodes").unwrap().column("id").unwrap().n_unique().unwrap(), 50);
+        assert_eq!(
+            tables_df.get("edges").unwrap().column("update_date").unwrap().dtype(),
+            &polars::prelude::DataType::Date
+        );
+        assert_eq!(tables_df.get("edges").unwrap().column("predicate").unwrap().n_unique().unwrap(), 1);
+        assert!(
+            tables_df
+                .get("edges")
+                .unwrap()
+                .column("p_value")
+                .unwrap()
+                .as_materialized_series()
+                .max::<f64>()
+                .unwrap()
+                .unwrap()
+                <= 0.05
+        );
+
+        // same seed, same tables; subject and object are distinct streams despite sharing a seed
+        let again_df = crate::fabricate_tables(&tables, 42).unwrap();
+        assert!(tables_df.get("edges").unwrap().equals(again_df.get("edges").unwrap()));
+        let edges_df = tables_df.get("edges").unwrap();
+        assert!(!edges_df
+            .column("subject")
+            .unwrap()
+            .as_materialized_series()
+            .equals(edges_df.column("object").unwrap().as_materialized_series()));
+    }
+
+    #[test]
+    fn test_edges_map_without_node_categories() {
+        let nodes_df = df!("id" => ["MONDO:1", "CHEBI:1"]).unwrap();
+        let edges_df = df!(
+            "subject" => ["CHEBI:1", "CHEBI:1"],
+            "predicate" => ["biolink:treats", "biolink:treats"],
+            "object" => ["MONDO:1", "MONDO:1"],
+            "knowledge_level" => ["knowledge_assertion", "prediction"],
+        )
+        .unwrap();
+        let column_map_options = crate::ColumnMapOptions {
+            distinct_threshold: 50,
+            text_policy: crate::TextPolicy::Synthesize,
+            column_policy: vec![],
+            free_text_min_length: 40.0,
+            free_text_min_tokens: 2.0,
+            extended_schema: true,
+        };
+
+        let mut map = IndexMap::new();
+        map.insert("nodes".to_string(), crate::create_nodes_map(&nodes_df, &10, &column_map_options).unwrap());
+        map.insert(
+            "edges".to_string(),
+            crate::create_edges_map(&edges_df, &nodes_df, &20, &column_map_options).unwrap(),
+        );
+        let yaml = serde_yml::to_string(&map).unwrap();
+        assert!(!yaml.contains("generate_correlated_values"));
+        assert!(!yaml.contains("triples"));
+
+        // without triples the YAML must still be accepted by fabricate
+        let tables: IndexMap<String, crate::TableSpec> = serde_yml::from_str(&yaml).unwrap();
+        let tables_df = crate::fabricate_tables(&tables, 42).unwrap();
+        assert_eq!(tables_df.get("edges").unwrap().height(), 20);
+    }
+
+    #[test]
+    fn test_extended_schema() {
+        let nodes_df = df!(
+            "id" => ["MONDO:0000001", "CHEBI:1"],
+            "category" => ["biolink:Disease", "biolink:SmallMolecule"],
+        )
+        .unwrap();
+        let edges_df = df!(
+            "subject" => ["CHEBI:1", "CHEBI:1"],
+            "predicate" => ["biolink:treats", "biolink:treats"],
+            "object" => ["MONDO:0000001", "MONDO:0000001"],
+            "knowledge_level" => ["knowledge_assertion", "prediction"],
+        )
+        .unwrap();
+        let build_yaml = |extended_schema: bool| {
+            let column_map_options = crate::ColumnMapOptions {
+                distinct_threshold: 50,
+                text_policy: crate::TextPolicy::Synthesize,
+                column_policy: vec![],
+                free_text_min_length: 40.0,
+                free_text_min_tokens: 2.0,
+                extended_schema,
+            };
+            let mut map = IndexMap::new();
+            map.insert("nodes".to_string(), crate::create_nodes_map(&nodes_df, &10, &column_map_options).unwrap());
+            map.insert(
+                "edges".to_string(),
+                crate::create_edges_map(&edges_df, &nodes_df, &20, &column_map_options).unwrap(),
+            );
+            serde_yml::to_string(&map).unwrap()
+        };
+
+        // the default output stays within the generator types the upstream fabricator accepts
+        let yaml = build_yaml(false);
+        assert!(!yaml.contains("by_category"));
+        assert!(!yaml.contains("generate_correlated_values"));
+        assert!(!yaml.contains("triples"));
+
+        let yaml = build_yaml(true);
+        assert!(yaml.contains("by_category"));
+        assert!(yaml.contains("generate_correlated_values"));
+        assert!(yaml.contains("triples"));
+    }
+
+    #[test]
+    fn test_learn_category_id_formats() {
+        let ids = ["MONDO:0000123", "MONDO:0004975", "NCBIGene:1017", "NCBIGene:672"].map(|a| a.to_string());
+        let categories = ["biolink:Disease", "biolink:Disease", "biolink:Gene", "biolink:Gene"].map(|a| a.to_string());
+        let by_category = crate::learn_category_id_formats(&ids, &categories);
+
+        let disease = by_category.get("biolink:Disease").unwrap();
+        assert_eq!(disease.prefixes.get("MONDO"), Some(&2));
+        assert_eq!(disease.id_length, Some(7));
+        assert!(disease.zero_padded);
+        assert_eq!(crate::format_curie("MONDO", "", 42, disease.id_length, disease.zero_padded), "MONDO:0000042");
+
+        let gene = by_category.get("biolink:Gene").unwrap();
+        assert!(!gene.zero_padded);
+        assert_eq!(crate::format_curie("NCBIGene", "", 1, gene.id_length, gene.zero_padded), "NCBIGene:1000");
+    }
+
+    #[test]
+    fn test_is_free_text() {
+        let descriptions = vec!["A rare disorder characterized by progressive loss of motor neurons.".to_string()];
+        assert!(crate::is_free_text(&descriptions, 40.0, 2.0));
+
+        let categories = vec!["biolink:Disease".to_string(), "biolink:SmallMolecule".to_string()];
+        assert!(!crate::is_free_text(&categories, 40.0, 2.0));
+    }
+
+    #[test]
+    fn test_long_arrays_are_not_free_text() {
+        let column_map_options = crate::ColumnMapOptions {
+            distinct_threshold: 50,
+            text_policy: crate::TextPolicy::Synthesize,
+            column_policy: vec![],
+            free_text_min_length: 40.0,
+            free_text_min_tokens: 2.0,
+            extended_schema: false,
+        };
+        let equivalent_identifiers = Series::new(
+            "equivalent_identifiers".into(),
+            [
+                "MONDO:0005148|DOID:9352|UMLS:C0011860|MESH:D003924|NCIT:C26747",
+                "MONDO:0005015|DOID:9351|UMLS:C0011849|MESH:D003920|NCIT:C2985",
+            ],
+        );
+        let column_map = crate::create_values_column_map("equivalent_identifiers", &equivalent_identifiers, &column_map_options);
+        assert_eq!(column_map.get("type"), Some(&to_value("generate_random_arrays").unwrap()));
+        assert_eq!(column_map.get("delimiter"), Some(&to_value("|").unwrap()));
+
+        // an explicit column policy still wins over list detection
+        let column_map_options = crate::ColumnMapOptions {
+            column_policy: vec!["equivalent_identifiers=drop".to_string()],
+            ..column_map_options
+        };
+        let column_map = crate::create_values_column_map("equivalent_identifiers", &equivalent_identifiers, &column_map_options);
+        assert!(column_map.is_empty());
+    }
+
+    #[test]
+    fn test_quantiles() {
+        let sorted = (0..=100).map(|a| a as f64).collect::<Vec<_>>();
+        assert_eq!(crate::quantiles(&sorted, 4), vec![0.0, 25.0, 50.0, 75.0, 100.0]);
+        assert_eq!(crate::quantiles(&[1.0, 2.0], 2), vec![1.0, 1.5, 2.0]);
+    }
+
+    #[test]
+    fn test_detect_array_delimiter() {
+        let separator = crate::SEPARATOR.as_str();
+        let categories = vec![format!("biolink:Gene{}biolink:NamedThing", separator), "biolink:Gene".to_string()];
+        assert_eq!(crate::detect_array_delimiter(&categories), Some(separator.to_string()));
+
+        let publications = vec!["PMID:1|PMID:2".to_string(), "PMID:3".to_string()];
+        assert_eq!(crate::detect_array_delimiter(&publications), Some("|".to_string()));
+
+        let names = vec!["aspirin".to_string(), "a | ".to_string()];
+        assert_eq!(crate::detect_array_delimiter(&names), None);
+    }
+
     #[test]
     fn scratch() {
         let mut category_column = IndexMap::new();
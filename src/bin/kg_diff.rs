@@ -0,0 +1,328 @@
+use clap::Parser;
+use humantime::format_duration;
+use itertools::Itertools;
+use log::{debug, info, warn};
+use polars::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Instant;
+use std::{error, fs, path};
+
+#[derive(Parser, PartialEq, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Options {
+    #[clap(short = 'a', long, required = true)]
+    old_nodes: path::PathBuf,
+
+    #[clap(short = 'b', long, required = true)]
+    old_edges: path::PathBuf,
+
+    #[clap(short = 'c', long, required = true)]
+    new_nodes: path::PathBuf,
+
+    #[clap(short = 'd', long, required = true)]
+    new_edges: path::PathBuf,
+
+    #[clap(short = 'o', long, required = true)]
+    output_dir: path::PathBuf,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct RecordsDiffSummary {
+    pub key_columns: Vec<String>,
+    pub old_count: usize,
+    pub new_count: usize,
+    pub old_duplicates: usize,
+    pub new_duplicates: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub columns_added: Vec<String>,
+    pub columns_removed: Vec<String>,
+    pub changed_by_column: BTreeMap<String, usize>,
+    pub added_by: BTreeMap<String, BTreeMap<String, usize>>,
+    pub removed_by: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct KGDiffSummary {
+    pub nodes: RecordsDiffSummary,
+    pub edges: RecordsDiffSummary,
+}
+
+fn main() -> Result<(), Box<dyn error::Error>> {
+    let start = Instant::now();
+    env_logger::init();
+
+    let options = Options::parse();
+    debug!("{:?}", options);
+
+    fs::create_dir_all(&options.output_dir).expect("Could not create output directory");
+
+    let nodes_summary = diff_records(
+        scan_kgx_file(&options.old_nodes),
+        scan_kgx_file(&options.new_nodes),
+        |_column_names| vec!["id".to_string()],
+        vec!["category"],
+        &options.output_dir,
+        "nodes",
+    )
+    .expect("Could not diff nodes");
+
+    // qualifiers are part of an edge's identity, so only columns outside the key are compared as properties
+    let edges_summary = diff_records(
+        scan_kgx_file(&options.old_edges),
+        scan_kgx_file(&options.new_edges),
+        |column_names| {
+            let mut keys = vec!["subject".to_string(), "predicate".to_string(), "object".to_string()];
+            keys.extend(column_names.iter().filter(|c| is_qualifier_column(c)).cloned().sorted());
+            keys
+        },
+        vec!["predicate", "primary_knowledge_source"],
+        &options.output_dir,
+        "edges",
+    )
+    .expect("Could not diff edges");
+
+    let summary = KGDiffSummary {
+        nodes: nodes_summary,
+        edges: edges_summary,
+    };
+
+    let summary_json = serde_json::to_string_pretty(&summary).unwrap();
+    fs::write(options.output_dir.join("summary.json"), &summary_json).expect("Could not write summary");
+    println!("{}", summary_json);
+
+    info!("Duration: {}", format_duration(start.elapsed()).to_string());
+    Ok(())
+}
+
+fn is_qualifier_column(column_name: &str) -> bool {
+    column_name.ends_with("_qualifier") || column_name.eq("qualified_predicate")
+}
+
+fn scan_kgx_file(path: &path::PathBuf) -> LazyFrame {
+    LazyCsvReader::new(path.clone())
+        .with_separator(b'\t')
+        .with_infer_schema_length(Some(0))
+        .with_truncate_ragged_lines(true)
+        .with_has_header(true)
+        .with_ignore_errors(true)
+        .finish()
+        .unwrap()
+}
+
+fn diff_records(
+    mut old_lf: LazyFrame,
+    mut new_lf: LazyFrame,
+    key_columns_fn: impl Fn(&Vec<String>) -> Vec<String>,
+    group_by_columns: Vec<&str>,
+    output_dir: &path::PathBuf,
+    name: &str,
+) -> Result<RecordsDiffSummary, Box<dyn error::Error>> {
+    let old_column_names = old_lf.collect_schema()?.iter_names().map(|a| a.to_string()).collect_vec();
+    let new_column_names = new_lf.collect_schema()?.iter_names().map(|a| a.to_string()).collect_vec();
+
+    let columns_added = new_column_names.iter().filter(|c| !old_column_names.contains(c)).cloned().collect_vec();
+    let columns_removed = old_column_names.iter().filter(|c| !new_column_names.contains(c)).cloned().collect_vec();
+
+    let all_column_names = old_column_names.iter().chain(new_column_names.iter()).unique().cloned().collect_vec();
+    let key_columns = key_columns_fn(&all_column_names);
+    debug!("{} key columns: {:?}", name, key_columns);
+
+    // a key column missing from one side (e.g. a qualifier introduced in the new release) is treated as null there
+    let fill_missing_keys = |lf: LazyFrame, column_names: &Vec<String>| -> LazyFrame {
+        let missing = key_columns
+            .iter()
+            .filter(|k| !column_names.contains(k))
+            .map(|k| lit(LiteralValue::untyped_null()).cast(DataType::String).alias(k.as_str()))
+            .collect_vec();
+        lf.with_columns(missing)
+    };
+
+    let old_lf = fill_missing_keys(old_lf, &old_column_names);
+    let new_lf = fill_missing_keys(new_lf, &new_column_names);
+
+    let unique_subset: Vec<PlSmallStr> = key_columns.iter().map(|k| k.as_str().into()).collect();
+
+    let old_count = rusty_matrix_io::count_rows(old_lf.clone());
+    let new_count = rusty_matrix_io::count_rows(new_lf.clone());
+
+    let old_lf = old_lf.unique_stable(Some(unique_subset.clone()), UniqueKeepStrategy::First);
+    let new_lf = new_lf.unique_stable(Some(unique_subset.clone()), UniqueKeepStrategy::First);
+
+    let old_duplicates = old_count - rusty_matrix_io::count_rows(old_lf.clone());
+    let new_duplicates = new_count - rusty_matrix_io::count_rows(new_lf.clone());
+    if old_duplicates > 0 || new_duplicates > 0 {
+        warn!("{} duplicate keys were dropped: old: {}, new: {}", name, old_duplicates, new_duplicates);
+    }
+
+    let compared_columns = old_column_names
+        .iter()
+        .filter(|c| new_column_names.contains(c) && !key_columns.contains(c))
+        .cloned()
+        .collect_vec();
+
+    let (added_lf, removed_lf, changed_lf) = diff_frames(old_lf, new_lf, &key_columns, &compared_columns);
+
+    let mut changed_by_column = BTreeMap::new();
+    if !compared_columns.is_empty() {
+        let counts_df = changed_lf
+            .clone()
+            .select(
+                compared_columns
+                    .iter()
+                    .map(|c| column_changed(c).cast(DataType::UInt64).sum().alias(c.as_str()))
+                    .collect_vec(),
+            )
+            .with_new_streaming(true)
+            .collect()
+            .unwrap();
+        for c in compared_columns.iter() {
+            let count: u64 = counts_df.column(c.as_str()).unwrap().get(0).unwrap().extract().unwrap_or(0);
+            if count > 0 {
+                changed_by_column.insert(c.clone(), count as usize);
+            }
+        }
+    }
+
+    let mut added_by = BTreeMap::new();
+    let mut removed_by = BTreeMap::new();
+    for group_by_column in group_by_columns.into_iter() {
+        if let Some(counts) = rusty_matrix_io::count_by_lazy(added_lf.clone(), group_by_column) {
+            added_by.insert(group_by_column.to_string(), counts);
+        }
+        if let Some(counts) = rusty_matrix_io::count_by_lazy(removed_lf.clone(), group_by_column) {
+            removed_by.insert(group_by_column.to_string(), counts);
+        }
+    }
+
+    let added = rusty_matrix_io::count_rows(added_lf.clone());
+    let removed = rusty_matrix_io::count_rows(removed_lf.clone());
+    let changed = rusty_matrix_io::count_rows(changed_lf.clone());
+    info!("{}: added: {}, removed: {}, changed: {}", name, added, removed, changed);
+
+    rusty_matrix_io::sink_tsv(added_lf, &output_dir.join(format!("{}_added.tsv", name)));
+    rusty_matrix_io::sink_tsv(removed_lf, &output_dir.join(format!("{}_removed.tsv", name)));
+    rusty_matrix_io::sink_tsv(changed_lf, &output_dir.join(format!("{}_changed.tsv", name)));
+
+    Ok(RecordsDiffSummary {
+        key_columns,
+        old_count,
+        new_count,
+        old_duplicates,
+        new_duplicates,
+        added,
+        removed,
+        changed,
+        columns_added,
+        columns_removed,
+        changed_by_column,
+        added_by,
+        removed_by,
+    })
+}
+
+fn column_changed(column: &String) -> Expr {
+    col(format!("{}_old", column).as_str()).neq_missing(col(format!("{}_new", column).as_str()))
+}
+
+/// Builds the added, removed and changed records lazily so neither KG has to fit in memory.
+fn diff_frames(old_lf: LazyFrame, new_lf: LazyFrame, key_columns: &[String], compared_columns: &[String]) -> (LazyFrame, LazyFrame, LazyFrame) {
+    let keys = key_columns.iter().map(|k| col(k.as_str())).collect_vec();
+
+    let anti_join = |left: LazyFrame, right: LazyFrame| -> LazyFrame {
+        left.join_builder()
+            .with(right.select(keys.clone()))
+            .left_on(keys.clone())
+            .right_on(keys.clone())
+            .how(JoinType::Anti)
+            .join_nulls(true)
+            .finish()
+    };
+
+    let added_lf = anti_join(new_lf.clone(), old_lf.clone());
+    let removed_lf = anti_join(old_lf.clone(), new_lf.clone());
+
+    let suffixed = |lf: LazyFrame, suffix: &str| -> LazyFrame {
+        lf.select(
+            keys.iter()
+                .cloned()
+                .chain(compared_columns.iter().map(|c| col(c.as_str()).alias(format!("{}_{}", c, suffix))))
+                .collect_vec(),
+        )
+    };
+
+    let changed_lf = match compared_columns.is_empty() {
+        true => old_lf.select(keys.clone()).slice(0, 0),
+        false => suffixed(old_lf, "old")
+            .join_builder()
+            .with(suffixed(new_lf, "new"))
+            .left_on(keys.clone())
+            .right_on(keys.clone())
+            .how(JoinType::Inner)
+            .join_nulls(true)
+            .finish()
+            .filter(any_horizontal(compared_columns.iter().map(column_changed).collect_vec()).unwrap())
+            .with_column(
+                concat_str(
+                    compared_columns
+                        .iter()
+                        .map(|c| when(column_changed(c)).then(lit(c.clone())).otherwise(lit(LiteralValue::untyped_null())))
+                        .collect_vec(),
+                    "|",
+                    true,
+                )
+                .alias("changed_columns"),
+            ),
+    };
+
+    (added_lf, removed_lf, changed_lf)
+}
+
+#[cfg(test)]
+mod test {
+    use polars::prelude::*;
+
+    fn collect_ids(lf: LazyFrame) -> Vec<String> {
+        let df = lf.sort(["id"], SortMultipleOptions::default()).collect().unwrap();
+        df.column("id").unwrap().str().unwrap().into_no_null_iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff_frames() {
+        let old_df = df!(
+            "id" => ["A:1", "A:2", "A:3"],
+            "name" => ["one", "two", "three"],
+        )
+        .unwrap();
+        let new_df = df!(
+            "id" => ["A:1", "A:3", "A:4"],
+            "name" => ["one", "THREE", "four"],
+        )
+        .unwrap();
+
+        let (added_lf, removed_lf, changed_lf) = crate::diff_frames(old_df.lazy(), new_df.lazy(), &["id".to_string()], &["name".to_string()]);
+
+        assert_eq!(collect_ids(added_lf), vec!["A:4"]);
+        assert_eq!(collect_ids(removed_lf), vec!["A:2"]);
+
+        // A:1 is unchanged, so only A:3 is reported with the column that differs
+        let changed_df = changed_lf.collect().unwrap();
+        assert_eq!(changed_df.height(), 1);
+        assert_eq!(changed_df.column("id").unwrap().str().unwrap().get(0), Some("A:3"));
+        assert_eq!(changed_df.column("changed_columns").unwrap().str().unwrap().get(0), Some("name"));
+    }
+
+    #[test]
+    fn test_diff_frames_without_compared_columns() {
+        let old_df = df!("id" => ["A:1", "A:2"]).unwrap();
+        let new_df = df!("id" => ["A:1", "A:2"]).unwrap();
+
+        let (added_lf, removed_lf, changed_lf) = crate::diff_frames(old_df.lazy(), new_df.lazy(), &["id".to_string()], &[]);
+
+        assert!(collect_ids(added_lf).is_empty());
+        assert!(collect_ids(removed_lf).is_empty());
+        assert_eq!(changed_lf.collect().unwrap().height(), 0);
+    }
+}
@@ -1,11 +1,152 @@
 use clap::{Parser, Subcommand};
 use humantime::format_duration;
-use log::{debug, info};
+use itertools::Itertools;
+use log::{debug, info, warn};
 use polars::prelude::*;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::io::Cursor;
 use std::time::Instant;
 use std::{error, fs, path};
 
+const PRIMEKG_INFORES: &str = "infores:primekg";
+const DEFAULT_PREDICATE_MAPPINGS: &str = include_str!("../../resources/primekg/predicate_mappings.tsv");
+const DEFAULT_PREFIX_RULES: &str = include_str!("../../resources/primekg/prefix_rules.tsv");
+const DEFAULT_FEATURE_COLUMNS: &str = include_str!("../../resources/primekg/feature_columns.tsv");
+
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum FeatureKind {
+    Text,
+    List,
+    Value,
+}
+
+/// Selects a drug or disease feature column and names the node property it becomes.
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+struct FeatureColumn {
+    features: String,
+    column: String,
+    property: String,
+    kind: FeatureKind,
+    delimiter: Option<String>,
+}
+
+/// Maps a PrimeKG node source onto a CURIE prefix, zero-padding the local id when `padding` is set.
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+struct PrefixRule {
+    source: String,
+    prefix: String,
+    padding: Option<usize>,
+    pattern: String,
+}
+
+/// One row of the PrimeKG relation to Biolink predicate mapping table; an empty `display_relation` matches any.
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+struct PredicateMapping {
+    relation: String,
+    display_relation: Option<String>,
+    predicate: String,
+    negated: Option<String>,
+    subject_aspect_qualifier: Option<String>,
+    subject_direction_qualifier: Option<String>,
+    object_aspect_qualifier: Option<String>,
+    object_direction_qualifier: Option<String>,
+    knowledge_level: Option<String>,
+    primary_knowledge_source: Option<String>,
+    agent_type: Option<String>,
+    symmetric: bool,
+    subject_type: Option<String>,
+}
+
+impl PredicateMapping {
+    const PROPERTIES: [&'static str; 9] = [
+        "predicate",
+        "negated",
+        "subject_aspect_qualifier",
+        "subject_direction_qualifier",
+        "object_aspect_qualifier",
+        "object_direction_qualifier",
+        "knowledge_level",
+        "primary_knowledge_source",
+        "agent_type",
+    ];
+
+    /// Columns besides the triple that tell two edges apart, so edges differing only in these are both kept.
+    const QUALIFIER_PROPERTIES: [&'static str; 5] = [
+        "negated",
+        "subject_aspect_qualifier",
+        "subject_direction_qualifier",
+        "object_aspect_qualifier",
+        "object_direction_qualifier",
+    ];
+
+    /// Columns that only steer `canonicalize_direction` and are not written out.
+    const DIRECTION_PROPERTIES: [&'static str; 2] = ["symmetric", "subject_type"];
+
+    fn property(&self, name: &str) -> Option<&str> {
+        match name {
+            "predicate" => Some(self.predicate.as_str()),
+            "negated" => self.negated.as_deref(),
+            "subject_aspect_qualifier" => self.subject_aspect_qualifier.as_deref(),
+            "subject_direction_qualifier" => self.subject_direction_qualifier.as_deref(),
+            "object_aspect_qualifier" => self.object_aspect_qualifier.as_deref(),
+            "object_direction_qualifier" => self.object_direction_qualifier.as_deref(),
+            "knowledge_level" => self.knowledge_level.as_deref(),
+            "primary_knowledge_source" => self.primary_knowledge_source.as_deref(),
+            "agent_type" => self.agent_type.as_deref(),
+            "symmetric" => Some(if self.symmetric { "true" } else { "false" }),
+            "subject_type" => self.subject_type.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn condition(&self) -> Expr {
+        let relation = col("relation").eq(lit(self.relation.as_str()));
+        match &self.display_relation {
+            Some(display_relation) => relation.and(col("display_relation").eq(lit(display_relation.as_str()))),
+            None => relation,
+        }
+    }
+}
+
+/// Counts written by `build` so the nodes and edges files can be checked against each other.
+#[derive(Serialize, PartialEq, Debug, Default)]
+struct ReconciliationReport {
+    nodes: usize,
+    edges: usize,
+    invalid_node_ids: usize,
+    invalid_subject_ids: usize,
+    invalid_object_ids: usize,
+    unmapped_relation_edges: usize,
+    reversed_edges: usize,
+    collapsed_symmetric_edges: usize,
+    collapsed_asymmetric_edges: usize,
+    ambiguous_direction_edges: usize,
+    dangling_edges: usize,
+    missing_endpoint_ids: usize,
+    missing_endpoint_examples: Vec<String>,
+    isolated_nodes: usize,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+struct GraphMetadataFile {
+    path: String,
+    sha256: String,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+struct GraphMetadata {
+    name: String,
+    knowledge_source: String,
+    node_count: usize,
+    edge_count: usize,
+    node_categories: BTreeMap<String, usize>,
+    predicates: BTreeMap<String, usize>,
+    inputs: Vec<GraphMetadataFile>,
+}
+
 #[derive(Parser, PartialEq, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Options {
@@ -15,12 +156,43 @@ struct Options {
 
 #[derive(Subcommand, PartialEq, Debug)]
 enum Commands {
+    Build {
+        #[arg(short = 'i', long, required = true)]
+        kg: path::PathBuf,
+
+        #[arg(short = 'a', long, required = true)]
+        drug_features: path::PathBuf,
+
+        #[arg(short = 'b', long, required = true)]
+        disease_features: path::PathBuf,
+
+        #[arg(short = 'n', long, required = true)]
+        nodes: path::PathBuf,
+
+        #[arg(short = 'o', long, required = true)]
+        output_dir: path::PathBuf,
+
+        #[arg(short = 'm', long)]
+        predicate_mappings: Option<path::PathBuf>,
+
+        #[arg(short = 'p', long)]
+        prefix_rules: Option<path::PathBuf>,
+
+        #[arg(short = 'f', long)]
+        feature_columns: Option<path::PathBuf>,
+    },
     BuildEdges {
         #[arg(short = 'i', long, required = true)]
         kg: path::PathBuf,
 
         #[arg(short = 'o', long, required = true)]
         output: path::PathBuf,
+
+        #[arg(short = 'm', long)]
+        predicate_mappings: Option<path::PathBuf>,
+
+        #[arg(short = 'p', long)]
+        prefix_rules: Option<path::PathBuf>,
     },
     BuildNodes {
         #[arg(short = 'a', long, required = true)]
@@ -34,10 +206,19 @@ enum Commands {
 
         #[arg(short = 'o', long, required = true)]
         output: path::PathBuf,
+
+        #[arg(short = 'p', long)]
+        prefix_rules: Option<path::PathBuf>,
+
+        #[arg(short = 'f', long)]
+        feature_columns: Option<path::PathBuf>,
     },
     PrintPredicateMappings {
         #[arg(short = 'i', long, required = true)]
         kg: path::PathBuf,
+
+        #[arg(short = 'm', long)]
+        predicate_mappings: Option<path::PathBuf>,
     },
 }
 
@@ -49,19 +230,47 @@ fn main() -> Result<(), Box<dyn error::Error>> {
     debug!("{:?}", options);
 
     match &options.command {
-        Some(Commands::BuildEdges { kg, output }) => {
-            build_edges(kg, output).expect("Could not build edges");
+        Some(Commands::Build {
+            kg,
+            drug_features,
+            disease_features,
+            nodes,
+            output_dir,
+            predicate_mappings,
+            prefix_rules,
+            feature_columns,
+        }) => {
+            let paths = BuildPaths {
+                kg,
+                drug_features,
+                disease_features,
+                nodes,
+                predicate_mappings,
+                prefix_rules,
+                feature_columns,
+            };
+            build(&paths, output_dir).expect("Could not build PrimeKG");
+        }
+        Some(Commands::BuildEdges {
+            kg,
+            output,
+            predicate_mappings,
+            prefix_rules,
+        }) => {
+            build_edges(kg, output, predicate_mappings, prefix_rules).expect("Could not build edges");
         }
         Some(Commands::BuildNodes {
             drug_features,
             disease_features,
             nodes,
             output,
+            prefix_rules,
+            feature_columns,
         }) => {
-            build_nodes(drug_features, disease_features, nodes, output).expect("Could not build nodes");
+            build_nodes(drug_features, disease_features, nodes, output, prefix_rules, feature_columns).expect("Could not build nodes");
         }
-        Some(Commands::PrintPredicateMappings { kg }) => {
-            print_predicate_mappings(kg).expect("Could not print predicate mappings");
+        Some(Commands::PrintPredicateMappings { kg, predicate_mappings }) => {
+            print_predicate_mappings(kg, predicate_mappings).expect("Could not print predicate mappings");
         }
         None => {}
     }
@@ -70,81 +279,26 @@ fn main() -> Result<(), Box<dyn error::Error>> {
     Ok(())
 }
 
-fn print_predicate_mappings(kg: &path::PathBuf) -> Result<(), Box<dyn error::Error>> {
-    let mut edges_df = LazyCsvReader::new(kg)
+fn print_predicate_mappings(kg: &path::PathBuf, predicate_mappings: &Option<path::PathBuf>) -> Result<(), Box<dyn error::Error>> {
+    let mappings = load_predicate_mappings(predicate_mappings);
+
+    let edges_lf = LazyCsvReader::new(kg)
         .with_infer_schema_length(Some(0))
         .with_ignore_errors(true)
         .with_truncate_ragged_lines(true)
         .with_has_header(true)
         .finish()
-        .unwrap()
-        .with_column(lit(LiteralValue::untyped_null()).cast(DataType::String).alias("predicate"))
-        .with_column(
-            when(col("relation").str().contains(
-                lit("^(bioprocess_protein|cellcomp_protein|exposure_bioprocess|exposure_cellcomp|exposure_molfunc|exposure_protein|molfunc_protein|pathway_protein)$"),
-                true,
-            ))
-                .then(lit("biolink:interacts_with"))
-                .otherwise(col("predicate"))
-                .alias("predicate"),
-        )
-        .with_column(
-            when(col("relation").str().contains(lit("^(disease_protein|phenotype_protein)$"), true))
-                .then(lit("biolink:associated_with"))
-                .otherwise(col("predicate"))
-                .alias("predicate"),
-        )
-        .with_column(
-            when(col("relation").str().contains(
-                lit("^(anatomy_anatomy|bioprocess_bioprocess|cellcomp_cellcomp|disease_disease|exposure_exposure|molfunc_molfunc|pathway_pathway|phenotype_phenotype)$"),
-                true,
-            ))
-                .then(lit("biolink:superclass_of"))
-                .otherwise(col("predicate"))
-                .alias("predicate"),
-        )
-        .with_column(
-            when(col("relation").eq(lit("protein_protein"))).then(lit("biolink:interacts_with")).otherwise(col("predicate")).alias("predicate"),
-        )
-        .with_column(
-            when(col("relation").eq(lit("drug_effect"))).then(lit("biolink:has_side_effect")).otherwise(col("predicate")).alias("predicate")
-        )
-        .with_column(
-            when(col("relation").eq(lit("contraindication"))).then(lit("biolink:contraindicated_in")).otherwise(col("predicate")).alias("predicate")
-        )
-        .with_column(when(col("relation").eq(lit("anatomy_protein_absent"))).then(lit("true")).otherwise(lit(LiteralValue::untyped_null())).alias("negated"))
-        .with_column(when(col("relation").eq(lit("anatomy_protein_absent"))).then(lit("biolink:expressed_in")).otherwise(col("predicate")).alias("predicate"))
-        .with_column(when(col("relation").eq(lit("anatomy_protein_present"))).then(lit("biolink:expressed_in")).otherwise(col("predicate")).alias("predicate"))
-        .with_column(when(col("relation").eq(lit("disease_phenotype_negative"))).then(lit("true")).otherwise(lit(LiteralValue::untyped_null())).alias("negated"))
-        .with_column(when(col("relation").eq(lit("disease_phenotype_negative"))).then(lit("biolink:has_phenotype")).otherwise(col("predicate")).alias("predicate"))
-        .with_column(when(col("relation").eq(lit("disease_phenotype_positive"))).then(lit("biolink:has_phenotype")).otherwise(col("predicate")).alias("predicate"))
-        .with_column(when(col("relation").eq(lit("exposure_disease"))).then(lit("biolink:correlated_with")).otherwise(col("predicate")).alias("predicate"))
-        .with_column(
-            when(col("relation").eq(lit("indication"))).then(lit("biolink:treats")).otherwise(col("predicate")).alias("predicate")
-        )
-        .with_column(
-            when(col("relation").eq(lit("off-label use"))).then(lit("biolink:applied_to_treat")).otherwise(col("predicate")).alias("predicate")
-        )
-        .with_column(
-            when(col("relation").eq(lit("drug_drug"))).then(lit("biolink:directly_physically_interacts_with")).otherwise(col("predicate")).alias("predicate")
-        )
-        .with_column(
-            when(col("relation").eq(lit("drug_protein")).and(col("display_relation").eq(lit("enzyme")))).then(lit("amount")).otherwise(lit(LiteralValue::untyped_null())).alias("subject_aspect_qualifier")
-        )
-        .with_column(
-            when(col("relation").eq(lit("drug_protein")).and(col("display_relation").eq(lit("enzyme")))).then(lit("biolink:affected_by")).otherwise(col("predicate")).alias("predicate")
-        )
-        .with_column(
-            when(col("relation").eq(lit("drug_protein")).and(col("display_relation").eq(lit("target")))).then(lit("biolink:directly_physically_interacts_with")).otherwise(col("predicate")).alias("predicate")
-        )
-        .with_column(
-            when(col("relation").eq(lit("drug_protein")).and(col("display_relation").eq(lit("carrier")))).then(lit("biolink:affected_by")).otherwise(col("predicate")).alias("predicate")
+        .unwrap();
+
+    let edges_df = apply_predicate_mappings(edges_lf, &mappings).collect().unwrap();
+    report_unmapped_relations(&edges_df);
+
+    let mut edges_df = edges_df
+        .lazy()
+        .unique(
+            Some(vec!["relation".into(), "display_relation".into(), "predicate".into()]),
+            UniqueKeepStrategy::First,
         )
-        .with_column(when(col("relation").eq(lit("drug_protein")).and(col("display_relation").eq(lit("transporter")))).then(lit("transport")).otherwise(lit(LiteralValue::untyped_null())).alias("subject_aspect_qualifier"))
-        .with_column(when(col("relation").eq(lit("drug_protein")).and(col("display_relation").eq(lit("transporter")))).then(lit("increased")).otherwise(lit(LiteralValue::untyped_null())).alias("subject_direction_qualifier"))
-        .with_column(
-            when(col("relation").eq(lit("drug_protein")).and(col("display_relation").eq(lit("transporter")))).then(lit("biolink:affected_by")).otherwise(col("predicate")).alias("predicate")
-        ).unique(Some(vec!["relation".into(), "display_relation".into(), "predicate".into()]), UniqueKeepStrategy::First)
         .select([col("relation"), col("display_relation"), col("predicate")])
         .collect()
         .unwrap();
@@ -164,7 +318,34 @@ fn build_nodes(
     disease_features: &path::PathBuf,
     nodes: &path::PathBuf,
     output: &path::PathBuf,
+    prefix_rules: &Option<path::PathBuf>,
+    feature_columns: &Option<path::PathBuf>,
 ) -> Result<(), Box<dyn error::Error>> {
+    let rules = load_prefix_rules(prefix_rules);
+    let feature_columns = load_feature_columns(feature_columns);
+    let mut main_df = nodes_frame(
+        drug_features,
+        disease_features,
+        nodes,
+        &rules,
+        &feature_columns,
+        &mut ReconciliationReport::default(),
+    );
+
+    let mut file = fs::File::create(output.as_path()).unwrap();
+    CsvWriter::new(&mut file).with_separator(b'\t').finish(&mut main_df).unwrap();
+
+    Ok(())
+}
+
+fn nodes_frame(
+    drug_features: &path::PathBuf,
+    disease_features: &path::PathBuf,
+    nodes: &path::PathBuf,
+    rules: &[PrefixRule],
+    feature_columns: &[FeatureColumn],
+    report: &mut ReconciliationReport,
+) -> DataFrame {
     let join_args = JoinArgs::new(JoinType::Full).with_coalesce(JoinCoalesce::CoalesceColumns);
     let mut main_df = df!("node_index" => &Vec::<String>::new()).unwrap();
 
@@ -193,6 +374,7 @@ fn build_nodes(
         .with_has_header(true)
         .finish()
         .unwrap();
+    let drug_features_df = select_feature_columns(drug_features_df, "drug", feature_columns);
 
     main_df = main_df
         .clone()
@@ -213,6 +395,7 @@ fn build_nodes(
         .with_has_header(true)
         .finish()
         .unwrap();
+    let disease_features_df = select_feature_columns(disease_features_df, "disease", feature_columns);
 
     main_df = main_df
         .clone()
@@ -225,39 +408,13 @@ fn build_nodes(
     debug!("column names: {:?}", main_df.get_column_names_str());
     debug!("adding disease features: {:?}", main_df.head(None));
 
+    // grouped diseases become one node per member, matching the edge endpoints
+    main_df = explode_mondo_grouped(main_df, "node_source", "node_id");
+
     main_df = main_df
         .clone()
         .lazy()
-        .with_column(
-            when(col("node_source").str().contains_literal(lit("NCBI")))
-                .then(concat_str([col("node_source"), col("node_id")], "Gene:", true))
-                .otherwise(col("node_source"))
-                .alias("node_source"),
-        )
-        .with_column(
-            when(col("node_source").str().contains_literal(lit("REACTOME")))
-                .then(concat_str([lit("REACT"), col("node_id")], ":", true))
-                .otherwise(col("node_source"))
-                .alias("node_source"),
-        )
-        .with_column(
-            when(col("node_source").str().contains(lit("^(HPO|MONDO|UBERON)$"), true))
-                .then(concat_str([col("node_source"), col("node_id").str().pad_start(7, '0')], ":", true))
-                .otherwise(col("node_source"))
-                .alias("node_source"),
-        )
-        .with_column(
-            when(col("node_source").str().contains(lit("^(CTD|GO|DrugBank)$"), true))
-                .then(concat_str([col("node_source"), col("node_id")], ":", true))
-                .otherwise(col("node_source"))
-                .alias("node_source"),
-        )
-        .with_column(
-            when(col("node_source").str().contains(lit("MONDO_grouped"), true))
-                .then(concat_str([lit("MONDO"), col("mondo_id").str().pad_start(7, '0')], ":", true))
-                .otherwise(col("node_source"))
-                .alias("node_source"),
-        )
+        .with_column(curie_expr("node_source", "node_id", rules).alias("node_source"))
         .with_column(
             when(col("node_type").str().contains_literal(lit("exposure")))
                 .then(lit("biolink:ChemicalExposure"))
@@ -319,18 +476,33 @@ fn build_nodes(
                 .alias("node_type"),
         )
         .drop(["node_id", "node_index"])
-        .rename(["node_source", "node_name", "category"], ["id", "name", "drug_category"], true)
+        .rename(["node_source", "node_name"], ["id", "name"], true)
         .rename(["node_type"], ["category"], true)
+        // a MONDO id can be a member of several grouped diseases
+        .unique_stable(Some(vec!["id".into()]), UniqueKeepStrategy::First)
         .collect()
         .unwrap();
 
-    let mut file = fs::File::create(output.as_path()).unwrap();
-    CsvWriter::new(&mut file).with_separator(b'\t').finish(&mut main_df).unwrap();
+    report.invalid_node_ids = report_invalid_ids(&main_df, "id", rules);
+    main_df
+}
+
+fn build_edges(
+    kg: &path::PathBuf,
+    output: &path::PathBuf,
+    predicate_mappings: &Option<path::PathBuf>,
+    prefix_rules: &Option<path::PathBuf>,
+) -> Result<(), Box<dyn error::Error>> {
+    let mappings = load_predicate_mappings(predicate_mappings);
+    let rules = load_prefix_rules(prefix_rules);
+    let mut edges_df = edges_frame(kg, &mappings, &rules, &mut ReconciliationReport::default());
 
+    let mut file = fs::File::create(output.as_path()).unwrap();
+    CsvWriter::new(&mut file).with_separator(b'\t').finish(&mut edges_df).unwrap();
     Ok(())
 }
 
-fn build_edges(kg: &path::PathBuf, output: &path::PathBuf) -> Result<(), Box<dyn error::Error>> {
+fn edges_frame(kg: &path::PathBuf, mappings: &[PredicateMapping], rules: &[PrefixRule], report: &mut ReconciliationReport) -> DataFrame {
     // relation,display_relation,x_index,x_id,x_type,x_name,x_source,y_index,y_id,y_type,y_name,y_source
     let mut edges_df = LazyCsvReader::new(kg)
         .with_infer_schema_length(Some(0))
@@ -343,208 +515,678 @@ fn build_edges(kg: &path::PathBuf, output: &path::PathBuf) -> Result<(), Box<dyn
         .unwrap();
 
     // need to explode MONDO_grouped
-    let mondo_grouped_exploded = |edges_df: DataFrame, x_or_y: String| -> DataFrame {
-        let source = format!("{}_source", x_or_y);
-        let id = format!("{}_id", x_or_y);
-        let source_mondo_grouped_df = edges_df
-            .clone()
-            .lazy()
-            .filter(col(source.as_str()).eq(lit("MONDO_grouped")))
-            .with_column(col(id.as_str()).str().split(lit("_")).alias(id.as_str()))
-            .with_column(
-                when(col(source.as_str()).eq(lit("MONDO_grouped")))
-                    .then(lit("MONDO"))
-                    .otherwise(col(source.as_str()))
-                    .alias(source.as_str()),
-            )
-            .explode([col(id)]);
-
-        let source_not_mondo_grouped = edges_df.clone().lazy().filter(col(source.as_str()).eq(lit("MONDO_grouped")).not());
-
-        concat([source_mondo_grouped_df, source_not_mondo_grouped], UnionArgs::default())
-            .unwrap()
-            .collect()
-            .unwrap()
-    };
-
-    edges_df = mondo_grouped_exploded(edges_df, "x".into());
-    edges_df = mondo_grouped_exploded(edges_df, "y".into());
+    edges_df = explode_mondo_grouped(edges_df, "x_source", "x_id");
+    edges_df = explode_mondo_grouped(edges_df, "y_source", "y_id");
 
     edges_df = edges_df
         .clone()
         .lazy()
         .with_columns([
-            lit("knowledge_assertion").alias("knowledge_level"),
-            lit("not_provided").alias("agent_type"),
-            lit("infores:primekg").alias("primary_knowledge_source"),
-            lit(LiteralValue::untyped_null()).cast(DataType::String).alias("aggregator_knowledge_source"),
             lit(LiteralValue::untyped_null()).cast(DataType::String).alias("original_subject"),
             lit(LiteralValue::untyped_null()).cast(DataType::String).alias("original_object"),
-            lit(LiteralValue::untyped_null()).cast(DataType::String).alias("negated"),
             lit(LiteralValue::untyped_null()).cast(DataType::String).alias("publications"),
-            lit(LiteralValue::untyped_null()).cast(DataType::String).alias("subject_aspect_qualifier"),
-            lit(LiteralValue::untyped_null()).cast(DataType::String).alias("subject_direction_qualifier"),
-            lit(LiteralValue::untyped_null()).cast(DataType::String).alias("object_aspect_qualifier"),
-            lit(LiteralValue::untyped_null()).cast(DataType::String).alias("object_direction_qualifier"),
-            lit(LiteralValue::untyped_null()).cast(DataType::String).alias("upstream_data_source"),
         ])
-        .with_column(
-            when(col("x_source").eq(lit("NCBI")))
-                .then(concat_str([col("x_source"), col("x_id")], "Gene:", true))
-                .otherwise(col("x_source"))
-                .alias("subject"),
-        )
-        .with_column(
-            when(col("x_source").str().contains(lit("REACTOME"), true))
-                .then(concat_str([lit("REACT"), col("x_id")], ":", true))
-                .otherwise(col("subject"))
-                .alias("subject"),
-        )
-        .with_column(
-            when(col("x_source").str().contains(lit("^(HPO|MONDO|UBERON)$"), true))
-                .then(concat_str([col("x_source"), col("x_id").str().pad_start(7, '0')], ":", true))
-                .otherwise(col("subject"))
-                .alias("subject"),
-        )
-        .with_column(
-            when(col("x_source").str().contains(lit("^(CTD|GO|DrugBank)$"), true))
-                .then(concat_str([col("x_source"), col("x_id")], ":", true))
-                .otherwise(col("subject"))
-                .alias("subject"),
-        )
-        .with_column(
-            when(col("y_source").eq(lit("NCBI")))
-                .then(concat_str([col("y_source"), col("y_id")], "Gene:", true))
-                .otherwise(col("y_source"))
-                .alias("object"),
-        )
-        .with_column(
-            when(col("y_source").str().contains(lit("REACTOME"), true))
-                .then(concat_str([lit("REACT"), col("y_id")], ":", true))
-                .otherwise(col("object"))
-                .alias("object"),
-        )
-        .with_column(
-            when(col("y_source").str().contains(lit("^(HPO||MONDO|UBERON)$"), true))
-                .then(concat_str([col("y_source"), col("y_id").str().pad_start(7, '0')], ":", true))
-                .otherwise(col("object"))
-                .alias("object"),
-        )
-        .with_column(
-            when(col("y_source").str().contains(lit("^(CTD|GO|DrugBank)$"), true))
-                .then(concat_str([col("y_source"), col("y_id")], ":", true))
-                .otherwise(col("object"))
-                .alias("object"),
-        )
+        .with_columns([
+            curie_expr("x_source", "x_id", rules).alias("subject"),
+            curie_expr("y_source", "y_id", rules).alias("object"),
+        ])
+        .collect()
+        .unwrap();
+
+    report.invalid_subject_ids = report_invalid_ids(&edges_df, "subject", rules);
+    report.invalid_object_ids = report_invalid_ids(&edges_df, "object", rules);
+
+    edges_df = apply_predicate_mappings(edges_df.lazy(), mappings).collect().unwrap();
+    report.unmapped_relation_edges = report_unmapped_relations(&edges_df);
+
+    edges_df = edges_df.lazy().filter(col("predicate").is_not_null()).collect().unwrap();
+    edges_df = canonicalize_direction(edges_df, report);
+
+    edges_df
+        .lazy()
         .drop([
-            "x_index", "x_id", "x_type", "x_name", "x_source", "y_index", "y_id", "y_type", "y_name", "y_source",
+            "relation",
+            "display_relation",
+            "x_index",
+            "x_id",
+            "x_type",
+            "x_name",
+            "x_source",
+            "y_index",
+            "y_id",
+            "y_type",
+            "y_name",
+            "y_source",
+            "symmetric",
+            "subject_type",
         ])
-        .rename(["relation"], ["predicate"], true)
-        // bioprocess_protein	interacts with
-        // cellcomp_protein	interacts with
-        // exposure_bioprocess	interacts with
-        // exposure_cellcomp	interacts with
-        // exposure_molfunc	interacts with
-        // exposure_protein	interacts with
-        // molfunc_protein	interacts with
-        // pathway_protein	interacts with
-        .with_column(
-            when(col("predicate").str().contains(
-                lit("^(bioprocess_protein|cellcomp_protein|exposure_bioprocess|exposure_cellcomp|exposure_molfunc|exposure_protein|molfunc_protein|pathway_protein)$"),
-                true,
-            ))
-                .then(lit("biolink:interacts_with"))
-                .otherwise(col("predicate"))
-                .alias("predicate"),
-        )
-        .with_column(
-            // disease_protein	associated with
-            // phenotype_protein	associated with
-            when(col("predicate").str().contains(lit("^(disease_protein|phenotype_protein)$"), true))
-                .then(lit("biolink:associated_with"))
-                .otherwise(col("predicate"))
-                .alias("predicate"),
-        )
-        // anatomy_anatomy	parent-child
-        // bioprocess_bioprocess	parent-child
-        // cellcomp_cellcomp	parent-child
-        // disease_disease	parent-child
-        // exposure_exposure	parent-child
-        // molfunc_molfunc	parent-child
-        // pathway_pathway	parent-child
-        // phenotype_phenotype	parent-child
-        .with_column(
-            when(col("predicate").str().contains(
-                lit("^(anatomy_anatomy|bioprocess_bioprocess|cellcomp_cellcomp|disease_disease|exposure_exposure|molfunc_molfunc|pathway_pathway|phenotype_phenotype)$"),
-                true,
-            ))
-                .then(lit("biolink:superclass_of"))
-                .otherwise(col("predicate"))
-                .alias("predicate"),
-        )
-        // protein_protein	ppi
-        .with_column(
-            when(col("predicate").eq(lit("protein_protein"))).then(lit("biolink:interacts_with")).otherwise(col("predicate")).alias("predicate"),
-        )
-        // drug_effect	side effect
-        .with_column(
-            when(col("predicate").eq(lit("drug_effect"))).then(lit("biolink:has_side_effect")).otherwise(col("predicate")).alias("predicate")
-        )
-        // contraindication	contraindication
-        .with_column(
-            when(col("predicate").eq(lit("contraindication"))).then(lit("biolink:contraindicated_in")).otherwise(col("predicate")).alias("predicate")
-        )
-        // anatomy_protein_absent	expression absent	prediction	computational_model											NCBIGene:4948	UBERON:0001476
-        .with_column(when(col("predicate").eq(lit("anatomy_protein_absent"))).then(lit("true")).otherwise(lit(LiteralValue::untyped_null())).alias("negated"))
-        .with_column(when(col("predicate").eq(lit("anatomy_protein_absent"))).then(lit("biolink:expressed_in")).otherwise(col("predicate")).alias("predicate"))
-        // anatomy_protein_present	expression present	prediction	computational_model											NCBIGene:81887	UBERON:0001323
-        .with_column(when(col("predicate").eq(lit("anatomy_protein_present"))).then(lit("biolink:expressed_in")).otherwise(col("predicate")).alias("predicate"))
-        // disease_phenotype_negative	phenotype absent	prediction	computational_model											MONDO:0019309	HPO:0004386
-        .with_column(when(col("predicate").eq(lit("disease_phenotype_negative"))).then(lit("true")).otherwise(lit(LiteralValue::untyped_null())).alias("negated"))
-        .with_column(when(col("predicate").eq(lit("disease_phenotype_negative"))).then(lit("biolink:has_phenotype")).otherwise(col("predicate")).alias("predicate"))
-        // disease_phenotype_positive	phenotype present	prediction	computational_model											MONDO:0007058	HPO:0009611
-        .with_column(when(col("predicate").eq(lit("disease_phenotype_positive"))).then(lit("biolink:has_phenotype")).otherwise(col("predicate")).alias("predicate"))
-        // exposure_disease	linked to	prediction	computational_model											CTD:C051786	MONDO:0002691
-        .with_column(when(col("predicate").eq(lit("exposure_disease"))).then(lit("biolink:correlated_with")).otherwise(col("predicate")).alias("predicate"))
-        // indication    indication      prediction      computational_model                                                                                     DrugBank:DB00264        MONDO:0005044
-        .with_column(
-            when(col("predicate").eq(lit("indication"))).then(lit("biolink:treats")).otherwise(col("predicate")).alias("predicate")
-        )
-        // off-label use        off-label use   prediction      computational_model                                                                                     DrugBank:DB00796        MONDO:0005016
-        .with_column(
-            when(col("predicate").eq(lit("off-label use"))).then(lit("biolink:applied_to_treat")).otherwise(col("predicate")).alias("predicate")
+        .collect()
+        .unwrap()
+}
+
+/// Puts every edge in its canonical direction and collapses the copies PrimeKG stores both ways round.
+fn canonicalize_direction(edges_df: DataFrame, report: &mut ReconciliationReport) -> DataFrame {
+    let is_symmetric = col("symmetric").eq(lit("true"));
+    // the subject_type of the mapping decides direction; symmetric edges between nodes of the same type are ordered by id
+    let reversed = col("subject_type").is_not_null().and(col("x_type").neq(col("subject_type")));
+    let reordered = col("subject_type").is_null().and(is_symmetric.clone()).and(col("subject").gt(col("object")));
+    let swap = reversed.clone().or(reordered);
+
+    let oriented_df = edges_df
+        .lazy()
+        .with_columns([
+            when(swap.clone()).then(col("object")).otherwise(col("subject")).alias("subject"),
+            when(swap).then(col("subject")).otherwise(col("object")).alias("object"),
+            reversed.fill_null(lit(false)).alias("reversed"),
+        ])
+        .collect()
+        .unwrap();
+    let symmetric_count = |df: &DataFrame| df.clone().lazy().filter(is_symmetric.clone()).collect().unwrap().height();
+
+    // once oriented, the copies PrimeKG stores both ways round share subject, predicate, object and qualifiers
+    let key = ["subject", "predicate", "object"]
+        .into_iter()
+        .chain(PredicateMapping::QUALIFIER_PROPERTIES)
+        .map(PlSmallStr::from)
+        .collect_vec();
+    let canonical_df = oriented_df
+        .clone()
+        .lazy()
+        .unique_stable(Some(key), UniqueKeepStrategy::First)
+        .collect()
+        .unwrap();
+
+    // asymmetric edges without a subject_type have no rule to orient them, so both directions are kept and reported
+    let low = when(col("subject").lt_eq(col("object"))).then(col("subject")).otherwise(col("object"));
+    let high = when(col("subject").lt_eq(col("object"))).then(col("object")).otherwise(col("subject"));
+    let pair = [low, col("predicate"), high]
+        .into_iter()
+        .chain(PredicateMapping::QUALIFIER_PROPERTIES.map(col))
+        .collect_vec();
+    let ambiguous_edges = canonical_df
+        .clone()
+        .lazy()
+        .filter(is_symmetric.clone().not().and(col("subject_type").is_null()))
+        .filter(col("subject").n_unique().over(pair).gt(lit(1)))
+        .collect()
+        .unwrap()
+        .height();
+
+    report.reversed_edges = oriented_df.column("reversed").unwrap().bool().unwrap().sum().unwrap_or_default() as usize;
+    report.collapsed_symmetric_edges = symmetric_count(&oriented_df) - symmetric_count(&canonical_df);
+    report.collapsed_asymmetric_edges = (oriented_df.height() - canonical_df.height()) - report.collapsed_symmetric_edges;
+    report.ambiguous_direction_edges = ambiguous_edges;
+    info!(
+        "reversed {} edges; collapsed {} symmetric and {} asymmetric duplicate edges",
+        report.reversed_edges, report.collapsed_symmetric_edges, report.collapsed_asymmetric_edges
+    );
+    if ambiguous_edges > 0 {
+        warn!("{} asymmetric edges are listed both ways without a subject_type", ambiguous_edges);
+    }
+
+    canonical_df.drop("reversed").unwrap()
+}
+
+/// Splits `MONDO_grouped` ids such as `8019_8020` into one `MONDO` row per member id.
+fn explode_mondo_grouped(df: DataFrame, source: &str, id: &str) -> DataFrame {
+    let mondo_grouped_df = df
+        .clone()
+        .lazy()
+        .filter(col(source).eq(lit("MONDO_grouped")))
+        .with_column(col(id).str().split(lit("_")).alias(id))
+        .with_column(lit("MONDO").alias(source))
+        .explode([col(id)]);
+
+    let not_mondo_grouped_df = df.lazy().filter(col(source).eq(lit("MONDO_grouped")).not());
+
+    concat([mondo_grouped_df, not_mondo_grouped_df], UnionArgs::default())
+        .unwrap()
+        .collect()
+        .unwrap()
+}
+
+/// Input files and mapping overrides for `build`.
+struct BuildPaths<'a> {
+    kg: &'a path::PathBuf,
+    drug_features: &'a path::PathBuf,
+    disease_features: &'a path::PathBuf,
+    nodes: &'a path::PathBuf,
+    predicate_mappings: &'a Option<path::PathBuf>,
+    prefix_rules: &'a Option<path::PathBuf>,
+    feature_columns: &'a Option<path::PathBuf>,
+}
+
+fn build(paths: &BuildPaths, output_dir: &path::PathBuf) -> Result<(), Box<dyn error::Error>> {
+    let mappings = load_predicate_mappings(paths.predicate_mappings);
+    let rules = load_prefix_rules(paths.prefix_rules);
+    let feature_columns = load_feature_columns(paths.feature_columns);
+    let mut report = ReconciliationReport::default();
+
+    let mut nodes_df = nodes_frame(paths.drug_features, paths.disease_features, paths.nodes, &rules, &feature_columns, &mut report);
+    let edges_df = edges_frame(paths.kg, &mappings, &rules, &mut report);
+    let mut edges_df = reconcile_edges(&nodes_df, edges_df, &mut report);
+
+    fs::create_dir_all(output_dir)?;
+    let nodes_output = output_dir.join("nodes.tsv");
+    let edges_output = output_dir.join("edges.tsv");
+    let mut file = fs::File::create(nodes_output.as_path()).unwrap();
+    CsvWriter::new(&mut file).with_separator(b'\t').finish(&mut nodes_df).unwrap();
+    let mut file = fs::File::create(edges_output.as_path()).unwrap();
+    CsvWriter::new(&mut file).with_separator(b'\t').finish(&mut edges_df).unwrap();
+
+    fs::write(output_dir.join("reconciliation_report.json"), serde_json::to_string_pretty(&report)?)?;
+
+    let metadata = GraphMetadata {
+        name: "PrimeKG".into(),
+        knowledge_source: PRIMEKG_INFORES.into(),
+        node_count: nodes_df.height(),
+        edge_count: edges_df.height(),
+        node_categories: rusty_matrix_io::count_by(&nodes_df, "category").unwrap_or_default(),
+        predicates: rusty_matrix_io::count_by(&edges_df, "predicate").unwrap_or_default(),
+        inputs: [paths.kg, paths.nodes, paths.drug_features, paths.disease_features]
+            .iter()
+            .map(|path| GraphMetadataFile {
+                path: path.to_string_lossy().to_string(),
+                sha256: rusty_matrix_io::sha256_digest(path),
+            })
+            .collect(),
+    };
+    fs::write(output_dir.join("graph_metadata.json"), serde_json::to_string_pretty(&metadata)?)?;
+
+    info!(
+        "wrote {} nodes and {} edges; dropped {} edges referencing {} missing nodes",
+        report.nodes, report.edges, report.dangling_edges, report.missing_endpoint_ids
+    );
+    Ok(())
+}
+
+/// Drops edges whose subject or object is not a node, recording the dangling ids and the nodes left without edges.
+fn reconcile_edges(nodes_df: &DataFrame, edges_df: DataFrame, report: &mut ReconciliationReport) -> DataFrame {
+    let node_ids = nodes_df.clone().lazy().select([col("id")]).unique(None, UniqueKeepStrategy::First);
+    let endpoints = concat(
+        [
+            edges_df.clone().lazy().select([col("subject").alias("id")]),
+            edges_df.clone().lazy().select([col("object").alias("id")]),
+        ],
+        UnionArgs::default(),
+    )
+    .unwrap()
+    .unique(None, UniqueKeepStrategy::First);
+
+    let edge_count = edges_df.height();
+    let missing_df = endpoints
+        .clone()
+        .join(node_ids.clone(), [col("id")], [col("id")], JoinArgs::new(JoinType::Anti))
+        .sort(["id"], SortMultipleOptions::default())
+        .collect()
+        .unwrap();
+    let reconciled_df = edges_df
+        .lazy()
+        .join(node_ids.clone(), [col("subject")], [col("id")], JoinArgs::new(JoinType::Semi))
+        .join(node_ids, [col("object")], [col("id")], JoinArgs::new(JoinType::Semi))
+        .collect()
+        .unwrap();
+    let isolated_nodes = nodes_df
+        .clone()
+        .lazy()
+        .join(endpoints, [col("id")], [col("id")], JoinArgs::new(JoinType::Anti))
+        .collect()
+        .unwrap()
+        .height();
+
+    let missing_ids = missing_df.column("id").unwrap().str().unwrap();
+    report.nodes = nodes_df.height();
+    report.edges = reconciled_df.height();
+    report.dangling_edges = edge_count - reconciled_df.height();
+    report.missing_endpoint_ids = missing_df.height();
+    report.missing_endpoint_examples = missing_ids.iter().flatten().take(20).map(String::from).collect();
+    report.isolated_nodes = isolated_nodes;
+    if report.dangling_edges > 0 {
+        warn!(
+            "dropped {} edges referencing {} ids missing from the nodes",
+            report.dangling_edges, report.missing_endpoint_ids
+        );
+    }
+    reconciled_df
+}
+
+fn read_tsv_resource<T: DeserializeOwned>(path: &Option<path::PathBuf>, default: &str) -> Vec<T> {
+    let content = match path {
+        Some(path) => fs::read_to_string(path).unwrap_or_else(|_| panic!("Could not read {}", path.display())),
+        None => default.to_string(),
+    };
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(b'\t')
+        .comment(Some(b'#'))
+        .from_reader(content.as_bytes());
+    rdr.deserialize().map(|result| result.expect("Could not parse resource row")).collect()
+}
+
+fn load_predicate_mappings(predicate_mappings: &Option<path::PathBuf>) -> Vec<PredicateMapping> {
+    read_tsv_resource(predicate_mappings, DEFAULT_PREDICATE_MAPPINGS)
+}
+
+fn load_prefix_rules(prefix_rules: &Option<path::PathBuf>) -> Vec<PrefixRule> {
+    read_tsv_resource(prefix_rules, DEFAULT_PREFIX_RULES)
+}
+
+fn load_feature_columns(feature_columns: &Option<path::PathBuf>) -> Vec<FeatureColumn> {
+    read_tsv_resource(feature_columns, DEFAULT_FEATURE_COLUMNS)
+}
+
+/// Keeps `node_index` plus the configured columns of one feature file, cleaned and renamed to their node properties.
+fn select_feature_columns(mut features_lf: LazyFrame, features: &str, feature_columns: &[FeatureColumn]) -> LazyFrame {
+    let schema = features_lf.collect_schema().unwrap();
+    let mut exprs = vec![col("node_index")];
+    for feature_column in feature_columns.iter().filter(|feature_column| feature_column.features == features) {
+        if schema.get(feature_column.column.as_str()).is_none() {
+            warn!("{} features have no {} column", features, feature_column.column);
+            continue;
+        }
+        exprs.push(feature_expr(feature_column).alias(feature_column.property.as_str()));
+    }
+    features_lf.select(exprs)
+}
+
+fn feature_expr(feature_column: &FeatureColumn) -> Expr {
+    let value = col(feature_column.column.as_str());
+    let cleaned = match feature_column.kind {
+        FeatureKind::Value => value,
+        // drop markup and collapse the embedded tabs and newlines that would otherwise break the TSV
+        FeatureKind::Text => value
+            .str()
+            .replace_all(lit("<[^>]*>"), lit(""), false)
+            .str()
+            .replace_all(lit(r"\s+"), lit(" "), false),
+        FeatureKind::List => {
+            let delimiter = regex_escape(feature_column.delimiter.as_deref().unwrap_or("|"));
+            value
+                .str()
+                .replace_all(lit(r"\s+"), lit(" "), false)
+                .str()
+                .replace_all(lit(format!(r"(\s*{}\s*)+", delimiter)), lit("|"), false)
+                .str()
+                .strip_chars(lit(" |"))
+        }
+    };
+    let cleaned = cleaned.str().strip_chars(lit(LiteralValue::untyped_null()));
+    when(cleaned.clone().str().len_chars().eq(lit(0)))
+        .then(lit(LiteralValue::untyped_null()).cast(DataType::String))
+        .otherwise(cleaned)
+}
+
+fn regex_escape(value: &str) -> String {
+    value.chars().fold(String::new(), |mut escaped, c| {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+/// Builds `prefix:local_id` from a source/id column pair; sources without a rule keep their raw name as the prefix.
+fn curie_expr(source_column: &str, id_column: &str, rules: &[PrefixRule]) -> Expr {
+    rules
+        .iter()
+        .rev()
+        .fold(concat_str([col(source_column), col(id_column)], ":", true), |otherwise, rule| {
+            let local_id = match rule.padding {
+                Some(padding) => col(id_column).str().pad_start(padding, '0'),
+                None => col(id_column),
+            };
+            when(col(source_column).eq(lit(rule.source.as_str())))
+                .then(concat_str([lit(rule.prefix.as_str()), local_id], ":", true))
+                .otherwise(otherwise)
+        })
+}
+
+/// Warns about ids that match none of the prefix rule patterns and returns how many there are.
+fn report_invalid_ids(df: &DataFrame, column: &str, rules: &[PrefixRule]) -> usize {
+    let valid = rules
+        .iter()
+        .fold(lit(false), |acc, rule| acc.or(col(column).str().contains(lit(rule.pattern.as_str()), true)));
+    let invalid_df = df
+        .clone()
+        .lazy()
+        .filter(valid.fill_null(lit(false)).not())
+        .select([col(column)])
+        .collect()
+        .unwrap();
+
+    if invalid_df.height() > 0 {
+        let invalid_ids = invalid_df.column(column).unwrap().str().unwrap();
+        let examples = invalid_ids.iter().map(|id| id.unwrap_or("null")).unique().take(5).join(", ");
+        warn!("{} {} values lack a valid prefix, e.g. {}", invalid_df.height(), column, examples);
+    }
+    invalid_df.height()
+}
+
+/// Adds the predicate, negation, qualifier and knowledge level columns; relations without a matching row get a null predicate.
+fn apply_predicate_mappings(edges_lf: LazyFrame, mappings: &[PredicateMapping]) -> LazyFrame {
+    let null = || lit(LiteralValue::untyped_null()).cast(DataType::String);
+    let exprs = PredicateMapping::PROPERTIES
+        .iter()
+        .chain(PredicateMapping::DIRECTION_PROPERTIES.iter())
+        .map(|property| {
+            // fold in reverse so the first matching row takes precedence
+            mappings
+                .iter()
+                .rev()
+                .fold(null(), |otherwise, mapping| {
+                    let value = mapping.property(property).map(lit).unwrap_or_else(null);
+                    when(mapping.condition()).then(value).otherwise(otherwise)
+                })
+                .alias(*property)
+        })
+        .collect::<Vec<_>>();
+
+    // PrimeKG aggregates the upstream resources; relations without one are attributed to PrimeKG itself
+    edges_lf.with_columns(exprs).with_columns([
+        col("primary_knowledge_source").fill_null(lit(PRIMEKG_INFORES)),
+        when(col("primary_knowledge_source").is_null())
+            .then(null())
+            .otherwise(lit(PRIMEKG_INFORES))
+            .alias("aggregator_knowledge_source"),
+        col("primary_knowledge_source").alias("upstream_data_source"),
+    ])
+}
+
+fn report_unmapped_relations(edges_df: &DataFrame) -> usize {
+    let unmapped_df = edges_df
+        .clone()
+        .lazy()
+        .filter(col("predicate").is_null())
+        .group_by([col("relation"), col("display_relation")])
+        .agg([len().cast(DataType::UInt64).alias("count")])
+        .sort(["relation", "display_relation"], SortMultipleOptions::default())
+        .collect()
+        .unwrap();
+
+    let relations = unmapped_df.column("relation").unwrap().str().unwrap();
+    let display_relations = unmapped_df.column("display_relation").unwrap().str().unwrap();
+    let counts = unmapped_df.column("count").unwrap().u64().unwrap();
+    let mut total = 0;
+    for ((relation, display_relation), count) in relations.iter().zip(display_relations.iter()).zip(counts.iter()) {
+        let count = count.unwrap_or_default() as usize;
+        warn!(
+            "unmapped relation: {} / {} ({} edges)",
+            relation.unwrap_or_default(),
+            display_relation.unwrap_or_default(),
+            count
+        );
+        total += count;
+    }
+    if total > 0 {
+        warn!("dropped {} edges with unmapped relations", total);
+    }
+    total
+}
+
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+    use polars::prelude::*;
+    use std::fs;
+
+    #[test]
+    fn test_apply_predicate_mappings() {
+        let mappings = crate::load_predicate_mappings(&None);
+        let edges_df = df!(
+            "relation" => ["drug_protein", "drug_protein", "anatomy_protein_absent", "disease_phenotype_positive", "drug_protein", "made_up"],
+            "display_relation" => ["enzyme", "transporter", "expression absent", "phenotype present", "unknown", "made up"],
         )
-        // drug_drug       synergistic interaction prediction      computational_model                                                                                     DrugBank:DB01431        DrugBank:DB06605
-        .with_column(
-            when(col("predicate").eq(lit("drug_drug"))).then(lit("biolink:directly_physically_interacts_with")).otherwise(col("predicate")).alias("predicate")
+        .unwrap();
+        let mapped_df = crate::apply_predicate_mappings(edges_df.lazy(), &mappings).collect().unwrap();
+
+        let predicates = mapped_df.column("predicate").unwrap().str().unwrap().iter().collect::<Vec<_>>();
+        assert_eq!(
+            predicates,
+            vec![
+                Some("biolink:affected_by"),
+                Some("biolink:affected_by"),
+                Some("biolink:expressed_in"),
+                Some("biolink:has_phenotype"),
+                None,
+                None
+            ]
+        );
+        let qualifiers = mapped_df.column("subject_aspect_qualifier").unwrap().str().unwrap().iter().collect::<Vec<_>>();
+        assert_eq!(qualifiers, vec![Some("amount"), Some("transport"), None, None, None, None]);
+        let negated = mapped_df.column("negated").unwrap().str().unwrap().iter().collect::<Vec<_>>();
+        assert_eq!(negated, vec![None, None, Some("true"), None, None, None]);
+        let sources = mapped_df.column("primary_knowledge_source").unwrap().str().unwrap().iter().collect::<Vec<_>>();
+        assert_eq!(sources[0], Some("infores:drugbank"));
+        assert_eq!(sources[2], Some("infores:bgee"));
+        let aggregators = mapped_df
+            .column("aggregator_knowledge_source")
+            .unwrap()
+            .str()
+            .unwrap()
+            .iter()
+            .collect::<Vec<_>>();
+        assert_eq!(aggregators[0], Some(crate::PRIMEKG_INFORES));
+        assert_eq!(crate::report_unmapped_relations(&mapped_df), 2);
+    }
+
+    #[test]
+    fn test_reconcile_edges() {
+        let nodes_df = df!("id" => ["MONDO:0008019", "MONDO:0008020", "MONDO:0005044", "HP:0009611"]).unwrap();
+        let edges_df = crate::explode_mondo_grouped(
+            df!(
+                "x_source" => ["MONDO_grouped", "MONDO", "NCBI"],
+                "x_id" => ["8019_8020", "5044", "9796"],
+                "y_source" => ["MONDO", "HPO", "MONDO"],
+                "y_id" => ["5044", "9611", "5044"],
+            )
+            .unwrap(),
+            "x_source",
+            "x_id",
+        );
+        let rules = crate::load_prefix_rules(&None);
+        let edges_df = edges_df
+            .lazy()
+            .select([
+                crate::curie_expr("x_source", "x_id", &rules).alias("subject"),
+                crate::curie_expr("y_source", "y_id", &rules).alias("object"),
+            ])
+            .collect()
+            .unwrap();
+        assert_eq!(edges_df.height(), 4);
+
+        let mut report = crate::ReconciliationReport::default();
+        let reconciled_df = crate::reconcile_edges(&nodes_df, edges_df, &mut report);
+        assert_eq!(reconciled_df.height(), 3);
+        assert_eq!(report.dangling_edges, 1);
+        assert_eq!(report.missing_endpoint_examples, vec!["NCBIGene:9796".to_string()]);
+        assert_eq!(report.isolated_nodes, 0);
+    }
+
+    /// Adds the qualifier columns `apply_predicate_mappings` would have set, left null unless the frame already has them.
+    fn with_qualifiers(edges_df: DataFrame) -> DataFrame {
+        let null = lit(LiteralValue::untyped_null()).cast(DataType::String);
+        let missing = crate::PredicateMapping::QUALIFIER_PROPERTIES
+            .iter()
+            .filter(|column| edges_df.column(column).is_err())
+            .map(|column| null.clone().alias(*column))
+            .collect::<Vec<_>>();
+        edges_df.lazy().with_columns(missing).collect().unwrap()
+    }
+
+    #[test]
+    fn test_canonicalize_direction() {
+        let edges_df = df!(
+            "subject" => ["NCBIGene:2", "NCBIGene:1", "DrugBank:DB00908", "NCBIGene:9796", "MONDO:0000001", "MONDO:0000002"],
+            "object" => ["NCBIGene:1", "NCBIGene:2", "NCBIGene:9796", "DrugBank:DB00908", "MONDO:0000002", "MONDO:0000001"],
+            "predicate" => ["biolink:interacts_with", "biolink:interacts_with", "biolink:affected_by", "biolink:affected_by", "biolink:causes", "biolink:causes"],
+            "x_type" => ["gene/protein", "gene/protein", "drug", "gene/protein", "disease", "disease"],
+            "symmetric" => ["true", "true", "false", "false", "false", "false"],
+            "subject_type" => [None, None, Some("drug"), Some("drug"), None, None],
         )
-        // drug_protein    enzyme  prediction      computational_model                                                                                     DrugBank:DB00908        NCBIGene:1565
-        .with_column(
-            when(col("predicate").eq(lit("drug_protein")).and(col("display_relation").eq(lit("enzyme")))).then(lit("amount")).otherwise(lit(LiteralValue::untyped_null())).alias("subject_aspect_qualifier")
+        .unwrap();
+
+        let mut report = crate::ReconciliationReport::default();
+        let canonical_df = crate::canonicalize_direction(with_qualifiers(edges_df), &mut report);
+        assert_eq!(canonical_df.height(), 4);
+        let subjects = canonical_df
+            .column("subject")
+            .unwrap()
+            .str()
+            .unwrap()
+            .iter()
+            .map(|id| id.unwrap())
+            .collect::<Vec<_>>();
+        // an asymmetric mapping without a subject_type has no rule to pick a direction, so both are kept and reported
+        assert_eq!(subjects, vec!["NCBIGene:1", "DrugBank:DB00908", "MONDO:0000001", "MONDO:0000002"]);
+        assert_eq!(report.reversed_edges, 1);
+        assert_eq!(report.collapsed_symmetric_edges, 1);
+        assert_eq!(report.collapsed_asymmetric_edges, 1);
+        assert_eq!(report.ambiguous_direction_edges, 2);
+    }
+
+    #[test]
+    fn test_canonicalize_direction_concept_hierarchy() {
+        let mappings = crate::load_predicate_mappings(&None);
+        let edges_df = df!(
+            "relation" => ["disease_disease", "disease_disease"],
+            "display_relation" => ["parent-child", "parent-child"],
+            "subject" => ["MONDO:0000002", "MONDO:0000001"],
+            "object" => ["MONDO:0000001", "MONDO:0000002"],
+            "x_type" => ["disease", "disease"],
         )
-        .with_column(
-            when(col("predicate").eq(lit("drug_protein")).and(col("display_relation").eq(lit("enzyme")))).then(lit("biolink:affected_by")).otherwise(col("predicate")).alias("predicate")
+        .unwrap();
+        let mapped_df = crate::apply_predicate_mappings(edges_df.lazy(), &mappings).collect().unwrap();
+
+        // the reversed copy comes first, yet the pair collapses to the same edge as it would in the other order
+        let mut report = crate::ReconciliationReport::default();
+        let canonical_df = crate::canonicalize_direction(mapped_df, &mut report);
+        assert_eq!(canonical_df.height(), 1);
+        let edge = ["subject", "predicate", "object"].map(|column| canonical_df.column(column).unwrap().str().unwrap().get(0).unwrap());
+        assert_eq!(edge, ["MONDO:0000001", "biolink:related_to_at_concept_level", "MONDO:0000002"]);
+        assert_eq!(report.collapsed_symmetric_edges, 1);
+        assert_eq!(report.ambiguous_direction_edges, 0);
+    }
+
+    #[test]
+    fn test_canonicalize_direction_keeps_qualified_edges() {
+        let edges_df = df!(
+            "subject" => ["DrugBank:DB00908", "DrugBank:DB00908", "DrugBank:DB00908", "NCBIGene:9796"],
+            "object" => ["NCBIGene:9796", "NCBIGene:9796", "NCBIGene:9796", "DrugBank:DB00908"],
+            "predicate" => ["biolink:affected_by", "biolink:affected_by", "biolink:affected_by", "biolink:affected_by"],
+            "subject_aspect_qualifier" => [Some("amount"), Some("transport"), Some("amount"), Some("amount")],
+            "negated" => [None, None, Some("true"), None],
+            "x_type" => ["drug", "drug", "drug", "gene/protein"],
+            "symmetric" => ["false", "false", "false", "false"],
+            "subject_type" => ["drug", "drug", "drug", "drug"],
         )
-        // drug_protein    target  prediction      computational_model                                                                                     DrugBank:DB00334        NCBIGene:1128
-        .with_column(
-            when(col("predicate").eq(lit("drug_protein")).and(col("display_relation").eq(lit("target")))).then(lit("biolink:directly_physically_interacts_with")).otherwise(col("predicate")).alias("predicate")
+        .unwrap();
+
+        // only the reversed copy of the first edge shares its triple, qualifiers and negation
+        let mut report = crate::ReconciliationReport::default();
+        let canonical_df = crate::canonicalize_direction(with_qualifiers(edges_df), &mut report);
+        assert_eq!(canonical_df.height(), 3);
+        let qualifiers = canonical_df
+            .column("subject_aspect_qualifier")
+            .unwrap()
+            .str()
+            .unwrap()
+            .iter()
+            .collect::<Vec<_>>();
+        assert_eq!(qualifiers, vec![Some("amount"), Some("transport"), Some("amount")]);
+        let negated = canonical_df.column("negated").unwrap().str().unwrap().iter().collect::<Vec<_>>();
+        assert_eq!(negated, vec![None, None, Some("true")]);
+        assert_eq!(report.collapsed_asymmetric_edges, 1);
+    }
+
+    #[test]
+    fn test_nodes_frame_shared_group_member() {
+        let test_dir = std::env::temp_dir().join("primekg_test_nodes_frame_shared_group_member");
+        fs::create_dir_all(&test_dir).unwrap();
+        let nodes = test_dir.join("nodes.csv");
+        fs::write(
+            &nodes,
+            "node_index,node_id,node_type,node_name,node_source\n\
+             0,9796,gene/protein,PHYHIP,NCBI\n\
+             1,8019_8020,disease,group one,MONDO_grouped\n\
+             2,8020_8021,disease,group two,MONDO_grouped\n",
         )
-        // drug_protein    carrier prediction      computational_model                                                                                     DrugBank:DB00451        NCBIGene:6906
-        .with_column(
-            when(col("predicate").eq(lit("drug_protein")).and(col("display_relation").eq(lit("carrier")))).then(lit("biolink:affected_by")).otherwise(col("predicate")).alias("predicate")
+        .unwrap();
+        let drug_features = test_dir.join("drug_features.csv");
+        fs::write(&drug_features, "node_index,description\n").unwrap();
+        let disease_features = test_dir.join("disease_features.csv");
+        fs::write(&disease_features, "node_index,group_name_bert\n1,group one\n2,group two\n").unwrap();
+
+        let mut report = crate::ReconciliationReport::default();
+        let rules = crate::load_prefix_rules(&None);
+        let feature_columns = crate::load_feature_columns(&None);
+        let nodes_df = crate::nodes_frame(&drug_features, &disease_features, &nodes, &rules, &feature_columns, &mut report);
+
+        // MONDO:0008020 belongs to both groups and is written once, with the attributes of the first group
+        let ids = nodes_df
+            .column("id")
+            .unwrap()
+            .str()
+            .unwrap()
+            .iter()
+            .map(|id| id.unwrap())
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["MONDO:0008019", "MONDO:0008020", "MONDO:0008021", "NCBIGene:9796"]);
+        let shared_df = nodes_df.clone().lazy().filter(col("id").eq(lit("MONDO:0008020"))).collect().unwrap();
+        assert_eq!(shared_df.column("name").unwrap().str().unwrap().get(0), Some("group one"));
+
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn test_select_feature_columns() {
+        let feature_columns = crate::load_feature_columns(&None);
+        let drug_features_df = df!(
+            "node_index" => ["1", "2"],
+            "description" => [Some("Quinidine is a <b>drug</b>.\n\tIt  works."), Some("  ")],
+            "category" => [Some("Antiarrhythmic ; Cinchona;; "), None],
+            "unused" => ["x", "y"],
         )
-        // drug_protein    transporter     prediction      computational_model                                                                                     DrugBank:DB00257        NCBIGene:10599
-        .with_column(when(col("predicate").eq(lit("drug_protein")).and(col("display_relation").eq(lit("transporter")))).then(lit("transport")).otherwise(lit(LiteralValue::untyped_null())).alias("subject_aspect_qualifier"))
-        .with_column(when(col("predicate").eq(lit("drug_protein")).and(col("display_relation").eq(lit("transporter")))).then(lit("increased")).otherwise(lit(LiteralValue::untyped_null())).alias("subject_direction_qualifier"))
-        .with_column(
-            when(col("predicate").eq(lit("drug_protein")).and(col("display_relation").eq(lit("transporter")))).then(lit("biolink:affected_by")).otherwise(col("predicate")).alias("predicate")
+        .unwrap();
+
+        let selected_df = crate::select_feature_columns(drug_features_df.lazy(), "drug", &feature_columns)
+            .collect()
+            .unwrap();
+        assert_eq!(selected_df.get_column_names_str(), vec!["node_index", "drug_description", "drug_category"]);
+        let descriptions = selected_df.column("drug_description").unwrap().str().unwrap().iter().collect::<Vec<_>>();
+        assert_eq!(descriptions, vec![Some("Quinidine is a drug. It works."), None]);
+        let categories = selected_df.column("drug_category").unwrap().str().unwrap().iter().collect::<Vec<_>>();
+        assert_eq!(categories, vec![Some("Antiarrhythmic|Cinchona"), None]);
+    }
+
+    #[test]
+    fn test_curie_expr() {
+        let rules = crate::load_prefix_rules(&None);
+        let nodes_df = df!(
+            "node_source" => ["NCBI", "HPO", "MONDO", "REACTOME", "GO", "DrugBank", "Unknown"],
+            "node_id" => ["9796", "9611", "5044", "R-HSA-9613829", "8150", "DB00908", "42"],
         )
-        .drop(["display_relation"])
-        .unique(Some(vec!["subject".into(), "predicate".into(), "object".into()]), UniqueKeepStrategy::First)
-        .collect()
         .unwrap();
+        let ids_df = nodes_df
+            .lazy()
+            .select([crate::curie_expr("node_source", "node_id", &rules).alias("id")])
+            .collect()
+            .unwrap();
 
-    let mut file = fs::File::create(output.as_path()).unwrap();
-    CsvWriter::new(&mut file).with_separator(b'\t').finish(&mut edges_df).unwrap();
-    Ok(())
+        let ids = ids_df.column("id").unwrap().str().unwrap().iter().map(|id| id.unwrap()).collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            vec![
+                "NCBIGene:9796",
+                "HP:0009611",
+                "MONDO:0005044",
+                "REACT:R-HSA-9613829",
+                "GO:0008150",
+                "DrugBank:DB00908",
+                "Unknown:42"
+            ]
+        );
+        assert_eq!(crate::report_invalid_ids(&ids_df, "id", &rules), 1);
+    }
 }
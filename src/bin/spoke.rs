@@ -7,14 +7,13 @@ use humantime::format_duration;
 use in_place::InPlace;
 use itertools::Itertools;
 use lazy_static::lazy_static;
-use log::{debug, info};
+use log::{debug, info, warn};
 use polars::prelude::*;
-use rayon::prelude::*;
 use reqwest::header;
 use reqwest::redirect::Policy;
 use serde_derive::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ffi::OsStr;
 use std::fs;
 use std::io::prelude::*;
@@ -24,6 +23,8 @@ use std::time::Duration;
 use std::time::Instant;
 use std::{error, io};
 
+const DEFAULT_PREDICATE_MAPPINGS: &str = include_str!("../../resources/spoke/predicate_mappings.tsv");
+
 lazy_static! {
     pub static ref REQWEST_CLIENT: AsyncOnce<reqwest::Client> = AsyncOnce::new(async {
         let mut headers = header::HeaderMap::new();
@@ -90,31 +91,121 @@ struct Options {
 #[derive(Subcommand, PartialEq, Debug)]
 enum Commands {
     Clean {
-        #[clap(short = 'p', long, required = true)]
-        base_path: path::PathBuf,
+        #[clap(short = 'p', long)]
+        base_path: Option<path::PathBuf>,
+
+        /// category to clean, repeat for more than one; defaults to the config categories
+        #[clap(short = 't', long = "category")]
+        categories: Vec<String>,
+
+        /// SPOKE predicate mapping TSV; defaults to the config predicate_mappings, then the bundled table
+        #[clap(short = 'm', long)]
+        predicate_mappings: Option<path::PathBuf>,
+
+        #[clap(short = 'c', long)]
+        config: Option<path::PathBuf>,
     },
     MergeEdges {
-        #[clap(short = 'n', long, required = true)]
-        nodes: path::PathBuf,
+        /// edge files to merge; defaults to the config edge_files
+        #[clap(short = 'e', long)]
+        edges: Vec<path::PathBuf>,
+
+        #[clap(short = 'o', long)]
+        output: Option<path::PathBuf>,
 
-        #[clap(short = 'e', long, required = true)]
-        edges: path::PathBuf,
+        #[clap(short = 'c', long)]
+        config: Option<path::PathBuf>,
     },
     MergeNodes {
-        #[arg(short = 'n', long, required = true)]
-        nodes: path::PathBuf,
+        /// node files to merge; defaults to the config node_files
+        #[arg(short = 'n', long)]
+        nodes: Vec<path::PathBuf>,
 
-        #[arg(short = 'e', long, required = true)]
-        edges: path::PathBuf,
+        #[arg(short = 'o', long)]
+        output: Option<path::PathBuf>,
 
-        #[arg(short, long, default_value_t = 10)]
-        limit: i32,
-
-        #[arg(short = 'o', long, required = true)]
-        output: path::PathBuf,
+        #[arg(short = 'c', long)]
+        config: Option<path::PathBuf>,
     },
 }
 
+/// Paths, file lists and category includes for the SPOKE pipeline, usually loaded from a YAML file.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpokeConfig {
+    pub base_path: Option<path::PathBuf>,
+    pub nodes_path: Option<path::PathBuf>,
+    pub edges_path: Option<path::PathBuf>,
+    pub categories: Vec<String>,
+    pub node_files: Vec<path::PathBuf>,
+    pub nodes_output: Option<path::PathBuf>,
+    pub edge_files: Vec<path::PathBuf>,
+    pub edges_output: Option<path::PathBuf>,
+    pub predicate_mappings: Option<path::PathBuf>,
+}
+
+impl SpokeConfig {
+    pub fn load(config: &Option<path::PathBuf>) -> SpokeConfig {
+        match config {
+            Some(config_path) => {
+                let config_content = fs::read_to_string(config_path).expect("Could not read SPOKE config");
+                serde_yml::from_str(&config_content).expect("Could not deserialize SPOKE config")
+            }
+            None => SpokeConfig::default(),
+        }
+    }
+
+    fn base_path(&self) -> path::PathBuf {
+        self.base_path
+            .clone()
+            .expect("A base path is required, set --base-path or base_path in the config")
+    }
+
+    pub fn nodes_path(&self) -> path::PathBuf {
+        self.nodes_path
+            .clone()
+            .map(|p| self.resolve(&p))
+            .unwrap_or_else(|| self.base_path().join("nodes"))
+    }
+
+    pub fn edges_path(&self) -> path::PathBuf {
+        self.edges_path
+            .clone()
+            .map(|p| self.resolve(&p))
+            .unwrap_or_else(|| self.base_path().join("edges"))
+    }
+
+    /// Relative paths are taken from `base_path` when one is configured.
+    fn resolve(&self, p: &path::Path) -> path::PathBuf {
+        match &self.base_path {
+            Some(base_path) if p.is_relative() => base_path.join(p),
+            _ => p.to_path_buf(),
+        }
+    }
+
+    pub fn node_files(&self) -> Vec<path::PathBuf> {
+        self.node_files
+            .iter()
+            .map(|p| if p.is_relative() { self.nodes_path().join(p) } else { p.clone() })
+            .collect()
+    }
+
+    pub fn edge_files(&self) -> Vec<path::PathBuf> {
+        self.edge_files
+            .iter()
+            .map(|p| if p.is_relative() { self.edges_path().join(p) } else { p.clone() })
+            .collect()
+    }
+
+    pub fn nodes_output(&self) -> Option<path::PathBuf> {
+        self.nodes_output.as_ref().map(|p| self.resolve(p))
+    }
+
+    pub fn edges_output(&self) -> Option<path::PathBuf> {
+        self.edges_output.as_ref().map(|p| self.resolve(p))
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn error::Error>> {
     let start = Instant::now();
@@ -124,14 +215,43 @@ async fn main() -> Result<(), Box<dyn error::Error>> {
     debug!("{:?}", options);
 
     match &options.command {
-        Some(Commands::MergeEdges { nodes, edges }) => {
-            merge_edges(nodes, edges).expect("Could not merge edges");
+        Some(Commands::MergeEdges { edges, output, config }) => {
+            let config = SpokeConfig::load(config);
+            let edge_files = if edges.is_empty() { config.edge_files() } else { edges.clone() };
+            let output = output
+                .clone()
+                .or_else(|| config.edges_output())
+                .expect("An output is required, set --output or edges_output in the config");
+            // the streaming engine drives its own async runtime, so it must not run on a tokio worker
+            tokio::task::block_in_place(|| merge_edges(&edge_files, &output)).expect("Could not merge edges");
         }
-        Some(Commands::MergeNodes { nodes, edges, limit, output }) => {
-            merge_nodes(nodes, edges, limit, output).expect("Could not merge nodes");
+        Some(Commands::MergeNodes { nodes, output, config }) => {
+            let config = SpokeConfig::load(config);
+            let node_files = if nodes.is_empty() { config.node_files() } else { nodes.clone() };
+            let output = output
+                .clone()
+                .or_else(|| config.nodes_output())
+                .expect("An output is required, set --output or nodes_output in the config");
+            // the streaming engine drives its own async runtime, so it must not run on a tokio worker
+            tokio::task::block_in_place(|| merge_nodes(&node_files, &output)).expect("Could not merge nodes");
         }
-        Some(Commands::Clean { base_path }) => {
-            clean_spoke_data(base_path).await.expect("Could not clean data");
+        Some(Commands::Clean {
+            base_path,
+            categories,
+            predicate_mappings,
+            config,
+        }) => {
+            let mut config = SpokeConfig::load(config);
+            if predicate_mappings.is_some() {
+                config.predicate_mappings = predicate_mappings.clone();
+            }
+            if base_path.is_some() {
+                config.base_path = base_path.clone();
+            }
+            if !categories.is_empty() {
+                config.categories = categories.clone();
+            }
+            clean_spoke_data(&config).await.expect("Could not clean data");
         }
         None => {}
     }
@@ -140,223 +260,32 @@ async fn main() -> Result<(), Box<dyn error::Error>> {
     Ok(())
 }
 
-fn merge_nodes(nodes: &path::PathBuf, edges: &path::PathBuf, limit: &i32, output: &path::PathBuf) -> Result<(), Box<dyn error::Error>> {
-    let base_path = path::PathBuf::from("/home/jdr0887/data/matrix/KGs/spoke/V5");
-    // let nodes_path = base_path.join("nodes");
-
-    // let first_output_path = base_path.join("first_merged_nodes.tsv");
-    // let second_output_path = base_path.join("second_merged_nodes.tsv");
-    // let third_output_path = base_path.join("third_merged_nodes.tsv");
-    // let fourth_output_path = base_path.join("fourth_merged_nodes.tsv");
-    // let fifth_output_path = base_path.join("fifth_merged_nodes.tsv");
-
-    // let first_node_file_names = vec![
-    //     "node_0_new.tsv",
-    //     "node_1_new.tsv",
-    //     "node_3_new.tsv",
-    //     "node_4_new.tsv",
-    //     "node_6_new.tsv",
-    //     "node_9_new.tsv",
-    //     "node_10_new.tsv",
-    //     "node_11_new.tsv",
-    //     "node_12_new.tsv", // doesn't have name...maybe use reference instead???
-    //     "node_13_new.tsv", // doesn't have name...maybe use accession instead???
-    //     "node_14_new.tsv",
-    //     "node_15_new.tsv",
-    //     "node_19_new.tsv",
-    //     "node_20_new.tsv",
-    //     "node_21_new.tsv",
-    //     "node_25_new.tsv", // doesn't have name...maybe use Allele_ID instead???
-    // ]
-    // .into_iter()
-    // .map(|a| base_path.join(a))
-    // .collect_vec();
-    //
-    // merge_nodes_files(&first_output_path, first_node_file_names);
-    //
-    // let second_node_file_names = vec![
-    //     "node_18_new_00.tsv",
-    //     "node_18_new_01.tsv",
-    //     "node_18_new_02.tsv",
-    //     "node_18_new_03.tsv",
-    //     "node_18_new_04.tsv",
-    //     "node_18_new_05.tsv",
-    //     "node_18_new_06.tsv",
-    //     "node_18_new_07.tsv",
-    //     "node_18_new_08.tsv",
-    //     "node_18_new_09.tsv",
-    // ]
-    // .into_iter()
-    // .map(|a| base_path.join(a))
-    // .collect_vec();
-    //
-    // merge_nodes_files(&second_output_path, second_node_file_names);
-    //
-    // let third_node_file_names = vec![
-    //     "node_18_new_10.tsv",
-    //     "node_18_new_11.tsv",
-    //     "node_18_new_12.tsv",
-    //     "node_18_new_13.tsv",
-    //     "node_18_new_14.tsv",
-    //     "node_18_new_15.tsv",
-    //     "node_18_new_16.tsv",
-    //     "node_18_new_17.tsv",
-    //     "node_18_new_18.tsv",
-    //     "node_18_new_19.tsv",
-    // ]
-    // .into_iter()
-    // .map(|a| base_path.join(a))
-    // .collect_vec();
-    //
-    // merge_nodes_files(&third_output_path, third_node_file_names);
-    //
-    // let fourth_node_file_names = vec![
-    //     "node_18_new_20.tsv",
-    //     "node_18_new_21.tsv",
-    //     "node_18_new_22.tsv",
-    //     "node_18_new_23.tsv",
-    //     "node_18_new_24.tsv",
-    //     "node_18_new_25.tsv",
-    //     "node_18_new_26.tsv",
-    //     "node_18_new_27.tsv",
-    //     "node_18_new_28.tsv",
-    //     "node_18_new_29.tsv",
-    // ]
-    // .into_iter()
-    // .map(|a| base_path.join(a))
-    // .collect_vec();
-    //
-    // merge_nodes_files(&fourth_output_path, fourth_node_file_names);
-    //
-    // let fifth_node_file_names = vec![
-    //     "node_18_new_30.tsv",
-    //     "node_18_new_31.tsv",
-    //     "node_18_new_32.tsv",
-    //     "node_18_new_33.tsv",
-    //     "node_18_new_34.tsv",
-    //     "node_18_new_35.tsv",
-    //     "node_18_new_36.tsv",
-    //     "node_18_new_37.tsv",
-    //     "node_18_new_38.tsv",
-    // ]
-    // .into_iter()
-    // .map(|a| base_path.join(a))
-    // .collect_vec();
-    //
-    // merge_files(JoinArgs::new(JoinType::Full).with_coalesce(JoinCoalesce::CoalesceColumns), &fifth_output_path, fifth_node_file_names);
-
-    // let part_1 = base_path.join("merged_nodes_1_2.tsv");
-    // merge_files(&part_1, vec![first_output_path.clone(), second_output_path]);
-    //
-    // let part_2 = base_path.join("merged_nodes_1_3.tsv");
-    // merge_files(&part_2, vec![first_output_path.clone(), third_output_path]);
-    //
-    // let part_3 = base_path.join("merged_nodes_1_4.tsv");
-    // merge_files(&part_3, vec![first_output_path.clone(), fourth_output_path]);
-    //
-    // let part_4 = base_path.join("merged_nodes_1_5.tsv");
-    // merge_files(&part_4, vec![first_output_path.clone(), fifth_output_path]);
-
-    // let part_one_df = LazyCsvReader::new(part_1.clone())
-    //     .with_separator(b'\t')
-    //     .with_truncate_ragged_lines(true)
-    //     .with_has_header(true)
-    //     .with_ignore_errors(true)
-    //     .finish()
-    //     .unwrap()
-    //     .with_columns([
-    //         col("vestige").strict_cast(DataType::String),
-    //         col("org_ncbi_id").strict_cast(DataType::String),
-    //         col("start").strict_cast(DataType::String),
-    //         col("end").strict_cast(DataType::String),
-    //     ])
-    //     .collect()
-    //     .unwrap();
-    //
-    // println!("column names: {:?}", part_one_df.get_column_names());
-    // println!("column types: {:?}", part_one_df.dtypes());
-    //
-    // let part_two_df = LazyCsvReader::new(part_2.clone())
-    //     .with_separator(b'\t')
-    //     .with_truncate_ragged_lines(true)
-    //     .with_has_header(true)
-    //     .with_ignore_errors(true)
-    //     .finish()
-    //     .unwrap()
-    //     .with_columns([
-    //         col("vestige").strict_cast(DataType::String),
-    //         col("org_ncbi_id").strict_cast(DataType::String),
-    //         col("start").strict_cast(DataType::String),
-    //         col("end").strict_cast(DataType::String),
-    //     ])
-    //     .collect()
-    //     .unwrap();
-    //
-    // println!("column names: {:?}", part_two_df.get_column_names());
-    // println!("column types: {:?}", part_two_df.dtypes());
-    //
-    // let part_three_df = LazyCsvReader::new(part_3.clone())
-    //     .with_separator(b'\t')
-    //     .with_truncate_ragged_lines(true)
-    //     .with_has_header(true)
-    //     .with_ignore_errors(true)
-    //     .finish()
-    //     .unwrap()
-    //     .with_columns([
-    //         col("vestige").strict_cast(DataType::String),
-    //         col("org_ncbi_id").strict_cast(DataType::String),
-    //         col("start").strict_cast(DataType::String),
-    //         col("end").strict_cast(DataType::String),
-    //     ])
-    //     .collect()
-    //     .unwrap();
-    //
-    // println!("column names: {:?}", part_three_df.get_column_names());
-    // println!("column types: {:?}", part_three_df.dtypes());
-    //
-    // let mut df_vertical_concat = concat([part_one_df.clone().lazy(), part_two_df.clone().lazy(), part_three_df.clone().lazy()], UnionArgs::default())
-    //     .unwrap()
-    //     .unique(Some(vec!["id".parse().unwrap(), "category".parse().unwrap()]), UniqueKeepStrategy::First)
-    //     .collect()
-    //     .unwrap();
-    //
-
-    let final_output = base_path.join("merged_nodes.tsv");
-    let final_df = LazyCsvReader::new(final_output.clone())
-        .with_separator(b'\t')
-        .with_truncate_ragged_lines(true)
-        .with_has_header(true)
-        .with_ignore_errors(true)
-        .finish()
-        .unwrap()
-        // .unique(Some(vec!["id".parse().unwrap(), "category".parse().unwrap()]), UniqueKeepStrategy::First)
-        .collect()
-        .unwrap();
-
-    println!("final_df.shape(): {:?}", final_df.shape());
-
-    // let final_output_distinct = base_path.join("merged_nodes_distinct.tsv");
-    // let mut file = fs::File::create(final_output_distinct.as_path()).unwrap();
-    // CsvWriter::new(&mut file).with_separator(b'\t').finish(&mut final_df).unwrap();
-
+fn merge_nodes(node_files: &[path::PathBuf], output: &path::PathBuf) -> Result<(), Box<dyn error::Error>> {
+    if node_files.is_empty() {
+        return Err("No node files to merge, set --nodes or node_files in the config".into());
+    }
+    merge_nodes_files(output, node_files.to_vec());
     Ok(())
 }
 
-async fn clean_spoke_data(base_path: &path::PathBuf) -> Result<(), Box<dyn error::Error>> {
+async fn clean_spoke_data(config: &SpokeConfig) -> Result<(), Box<dyn error::Error>> {
     let category_ancestor_mapping = create_category_mapping().await;
     debug!("{:?}", category_ancestor_mapping);
 
-    let nodes_path = base_path.join("nodes");
-    let edges_path = base_path.join("edges");
+    let nodes_path = config.nodes_path();
+    let edges_path = config.edges_path();
 
     let category_info_path = nodes_path.join("category_file_info.tsv");
-    let category_file_name_mapping: BTreeMap<String, String> = read_category_info(&category_info_path);
+    let category_file_name_mapping: BTreeMap<String, String> = read_category_info(&category_info_path, &config.categories);
     // println!("category_file_name_mapping: {:?}", category_file_name_mapping);
 
     let predicate_info_path = edges_path.join("predicate_file_info.tsv");
     let spo_file_name_mapping: BTreeMap<(String, String, String), String> = read_predicate_info(&predicate_info_path);
     // println!("spo_file_name_mapping: {:?}", spo_file_name_mapping);
 
+    let mut id_identifier_map: HashMap<String, String> = HashMap::new();
+    let mut edge_file_names: BTreeSet<String> = BTreeSet::new();
+
     for (category, file_name) in category_file_name_mapping.iter() {
         info!("category: {}, file_name: {}", category, file_name);
 
@@ -427,91 +356,194 @@ async fn clean_spoke_data(base_path: &path::PathBuf) -> Result<(), Box<dyn error
                 }
             }
         }
-        let id_identifier_map: HashMap<String, String> = nodes.into_par_iter().map(|n| (n.id.clone(), n.identifier.clone())).collect();
-
+        id_identifier_map.extend(nodes.into_iter().map(|n| (n.id, n.identifier)));
         info!("id_identifier_map.len(): {:?}", id_identifier_map.len());
+        edge_file_names.extend(edge_files_by_category);
+    }
 
-        // SECOND: UPDATE THE EDGES
-        for edge_file_name in edge_files_by_category.iter() {
-            let start_modifying_edge_file = Instant::now();
+    // SECOND: UPDATE THE EDGES, once per file now that ids from every category are known
+    let predicate_mappings = load_predicate_mappings(&config.predicate_mappings);
+    let mut total = CleanCounts::default();
+    for edge_file_name in edge_file_names.iter() {
+        let start_modifying_edge_file = Instant::now();
+        let edge_file_path = edges_path.join(format!("{}.tsv", edge_file_name));
+
+        let counts = clean_edge_file(&edge_file_path, &id_identifier_map, &predicate_mappings);
+        info!(
+            "{:?}: {} edges, {} endpoints remapped, {} unresolved",
+            edge_file_path, counts.edges, counts.remapped, counts.unresolved
+        );
+        for (predicate, count) in counts.unmapped_predicates.iter() {
+            warn!("{:?}: unmapped predicate {} ({} edges)", edge_file_path, predicate, count);
+        }
+        total.add(&counts);
 
-            let edge_file_path = edges_path.join(format!("{}.tsv", edge_file_name));
+        info!(
+            "Duration to write {:?}: {}",
+            edge_file_path,
+            format_duration(start_modifying_edge_file.elapsed()).to_string()
+        );
+    }
+    info!("{} edges, {} endpoints remapped, {} unresolved", total.edges, total.remapped, total.unresolved);
+    if total.unresolved > 0 {
+        warn!("{} edge endpoints have no identifier in the cleaned categories", total.unresolved);
+    }
+    if !total.unmapped_predicates.is_empty() {
+        warn!(
+            "{} SPOKE predicates have no Biolink mapping: {:?}",
+            total.unmapped_predicates.len(),
+            total.unmapped_predicates.keys().collect_vec()
+        );
+    }
+    Ok(())
+}
 
-            let mut edges = read_edges_file(&edge_file_path);
-            info!("edges.len(): {}", edges.len());
+/// Tallies from rewriting an edge file: endpoints remapped to identifiers and SPOKE predicates left unmapped.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct CleanCounts {
+    pub edges: usize,
+    pub remapped: usize,
+    pub unresolved: usize,
+    pub unmapped_predicates: BTreeMap<String, usize>,
+}
 
-            edges.par_iter_mut().for_each(|e| {
-                if let Some(map_value) = id_identifier_map.get(&e.subject) {
-                    e.subject = map_value.clone();
-                }
-            });
+impl CleanCounts {
+    fn add(&mut self, other: &CleanCounts) {
+        self.edges += other.edges;
+        self.remapped += other.remapped;
+        self.unresolved += other.unresolved;
+        for (predicate, count) in other.unmapped_predicates.iter() {
+            *self.unmapped_predicates.entry(predicate.clone()).or_default() += count;
+        }
+    }
+}
 
-            // id_identifier_map.iter().for_each(|(map_key, map_value)| {
-            //     edges.par_iter_mut().for_each(|e| {
-            //         if e.subject.eq(map_key) {
-            //             e.subject = map_value.clone();
-            //         }
-            //         if e.object.eq(map_key) {
-            //             e.object = map_value.clone();
-            //         }
-            //         if !e.predicate.starts_with("biolink:") {
-            //             e.predicate = format!("biolink:{}", e.predicate);
-            //         }
-            //     });
-            // });
-
-            edges.sort_by(|a, b| a.subject.cmp(&b.subject).then(a.object.cmp(&b.object)));
-
-            let edge_header = format!("subject\tobject\tpredicate\t{}", get_header_remainder(&edge_file_path, 3));
-            let edge_file_output = fs::File::create(edge_file_path.clone()).unwrap();
-            let mut edge_writer = BufWriter::new(edge_file_output);
-
-            writeln!(edge_writer, "{}", edge_header).expect("Could not write to edges file");
-
-            edges.iter().for_each(|e| {
-                writeln!(edge_writer, "{}\t{}\t{}\t{}", e.subject, e.object, e.predicate, e.remainder).unwrap();
-            });
-            // let mut df = LazyCsvReader::new(edge_file_path.clone())
-            //     .with_separator(b'\t')
-            //     .with_truncate_ragged_lines(true)
-            //     .with_has_header(true)
-            //     .with_ignore_errors(true)
-            //     .finish()
-            //     .unwrap()
-            //     .with_columns([col("subject").strict_cast(DataType::String), col("object").strict_cast(DataType::String)])
-            //     .collect()
-            //     .unwrap();
-            //
-            // id_identifier_map.iter().for_each(|(map_key, map_value)| {
-            //     df = df
-            //         .clone()
-            //         .lazy()
-            //         .with_columns([
-            //             when(col("subject").eq(lit(map_key.clone()))).then(lit(map_value.clone())).otherwise(col("subject")).alias("subject"),
-            //             when(col("object").eq(lit(map_key.clone()))).then(lit(map_value.clone())).otherwise(col("object")).alias("object"),
-            //         ])
-            //         // .with_column(lit("biolink:").append(col("predicate"), false))
-            //         .collect()
-            //         .unwrap();
-            // });
-            //
-            // df.apply("predicate", |c| {
-            //     c.str().unwrap().into_iter().map(|opt_name: Option<&str>| opt_name.map(|name: &str| format!("biolink:{}", name))).collect::<StringChunked>().into_column()
-            // })
-            // .expect("Could not modify df");
-            //
-            // let mut file = fs::File::create(edge_file_path.as_path()).unwrap();
-            // CsvWriter::new(&mut file).with_separator(b'\t').finish(&mut df).unwrap();
-            info!(
-                "Duration to write {:?}: {}",
-                edge_file_path,
-                format_duration(start_modifying_edge_file.elapsed()).to_string()
-            );
+/// One row of the SPOKE relationship type to Biolink predicate table.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct SpokePredicateMapping {
+    pub spoke_predicate: String,
+    pub predicate: String,
+    pub subject_aspect_qualifier: Option<String>,
+    pub subject_direction_qualifier: Option<String>,
+    pub object_aspect_qualifier: Option<String>,
+    pub object_direction_qualifier: Option<String>,
+    pub qualified_predicate: Option<String>,
+}
+
+impl SpokePredicateMapping {
+    const QUALIFIER_COLUMNS: [&'static str; 5] = [
+        "subject_aspect_qualifier",
+        "subject_direction_qualifier",
+        "object_aspect_qualifier",
+        "object_direction_qualifier",
+        "qualified_predicate",
+    ];
+
+    fn qualifiers(&self) -> [&str; 5] {
+        [
+            self.subject_aspect_qualifier.as_deref().unwrap_or_default(),
+            self.subject_direction_qualifier.as_deref().unwrap_or_default(),
+            self.object_aspect_qualifier.as_deref().unwrap_or_default(),
+            self.object_direction_qualifier.as_deref().unwrap_or_default(),
+            self.qualified_predicate.as_deref().unwrap_or_default(),
+        ]
+    }
+}
+
+fn load_predicate_mappings(predicate_mappings: &Option<path::PathBuf>) -> HashMap<String, SpokePredicateMapping> {
+    let content = match predicate_mappings {
+        Some(predicate_mappings_path) => fs::read_to_string(predicate_mappings_path).expect("Could not read SPOKE predicate mappings"),
+        None => DEFAULT_PREDICATE_MAPPINGS.to_string(),
+    };
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(b'\t')
+        .comment(Some(b'#'))
+        .from_reader(content.as_bytes());
+    rdr.deserialize::<SpokePredicateMapping>()
+        .map(|result| {
+            let mapping = result.expect("Could not parse SPOKE predicate mapping");
+            (mapping.spoke_predicate.clone(), mapping)
+        })
+        .collect()
+}
+
+fn clean_edge_file(
+    edge_file_path: &path::PathBuf,
+    id_identifier_map: &HashMap<String, String>,
+    predicate_mappings: &HashMap<String, SpokePredicateMapping>,
+) -> CleanCounts {
+    let inp = InPlace::new(edge_file_path.as_path()).open().expect("Could not open edge file in place");
+    let counts = clean_edges(
+        io::BufReader::new(inp.reader()),
+        BufWriter::new(inp.writer()),
+        id_identifier_map,
+        predicate_mappings,
+    );
+    inp.save().expect("Could not save edge file");
+    counts
+}
+
+/// Streams edges from `reader` to `writer` in a single pass, replacing the subject and object with their identifiers and the
+/// SPOKE predicate with its Biolink mapping and qualifiers.
+fn clean_edges<R: BufRead, W: Write>(
+    reader: R,
+    mut writer: W,
+    id_identifier_map: &HashMap<String, String>,
+    predicate_mappings: &HashMap<String, SpokePredicateMapping>,
+) -> CleanCounts {
+    let mut counts = CleanCounts::default();
+    let mut lines = reader.lines();
+    let header = lines.next().expect("Could not get header").unwrap();
+
+    // qualifier columns from an earlier clean are rewritten rather than appended twice
+    let header_remainder = header.split('\t').skip(3).collect_vec();
+    let kept_columns = (0..header_remainder.len())
+        .filter(|idx| !SpokePredicateMapping::QUALIFIER_COLUMNS.contains(&header_remainder[*idx]))
+        .collect_vec();
+    let qualifier_columns = SpokePredicateMapping::QUALIFIER_COLUMNS.map(|column| header_remainder.iter().position(|c| *c == column));
+    // joined as one list so an edge file with no extra columns does not gain an empty one
+    let header_columns = ["subject", "object", "predicate"]
+        .into_iter()
+        .chain(kept_columns.iter().map(|idx| header_remainder[*idx]))
+        .chain(SpokePredicateMapping::QUALIFIER_COLUMNS)
+        .join("\t");
+    writeln!(writer, "{}", header_columns).expect("Could not write to edges file");
+
+    for line in lines {
+        let line = line.unwrap();
+        let Some(mut edge) = parse_edge_line(&line) else {
+            continue;
+        };
+        for endpoint in [&mut edge.subject, &mut edge.object] {
+            match id_identifier_map.get(endpoint.as_str()) {
+                Some(identifier) => {
+                    *endpoint = identifier.clone();
+                    counts.remapped += 1;
+                }
+                None => counts.unresolved += 1,
+            }
         }
 
-        // break;
+        let remainder = edge.remainder.split('\t').collect_vec();
+        let mut qualifiers = qualifier_columns.map(|idx| idx.and_then(|idx| remainder.get(idx).copied()).unwrap_or_default());
+        if let Some(mapping) = predicate_mappings.get(&edge.predicate) {
+            edge.predicate = mapping.predicate.clone();
+            qualifiers = mapping.qualifiers();
+        } else if !edge.predicate.starts_with("biolink:") {
+            *counts.unmapped_predicates.entry(edge.predicate.clone()).or_default() += 1;
+        }
+        counts.edges += 1;
+
+        let columns = [edge.subject.as_str(), edge.object.as_str(), edge.predicate.as_str()]
+            .into_iter()
+            .chain(kept_columns.iter().map(|idx| remainder.get(*idx).copied().unwrap_or_default()))
+            .chain(qualifiers)
+            .join("\t");
+        writeln!(writer, "{}", columns).expect("Could not write to edges file");
     }
-    Ok(())
+    writer.flush().expect("Could not flush edges file");
+    counts
 }
 
 fn get_header_remainder(file_path: &path::PathBuf, split_at_idx: usize) -> String {
@@ -548,28 +580,18 @@ fn read_nodes_file(nodes_file_path: &path::PathBuf) -> Vec<Node> {
     nodes
 }
 
-fn read_edges_file(edges_file_path: &path::PathBuf) -> Vec<Edge> {
-    let edges_file = fs::File::open(edges_file_path).unwrap();
-    let reader = io::BufReader::new(edges_file);
-    let edges: Vec<Edge> = reader
-        .lines()
-        .skip(1)
-        .filter_map(|line| {
-            let line = line.unwrap();
-            let split = line.split('\t').collect_vec();
-            if split.get(2).is_some() {
-                let (left, right) = split.split_at(3);
-                return Some(Edge {
-                    subject: left[0].to_string(),
-                    predicate: left[2].to_string(),
-                    object: left[1].to_string(),
-                    remainder: right.join("\t"),
-                });
-            }
-            None
-        })
-        .collect();
-    edges
+fn parse_edge_line(line: &str) -> Option<Edge> {
+    let split = line.split('\t').collect_vec();
+    if split.get(2).is_some() {
+        let (left, right) = split.split_at(3);
+        return Some(Edge {
+            subject: left[0].to_string(),
+            predicate: left[2].to_string(),
+            object: left[1].to_string(),
+            remainder: right.join("\t"),
+        });
+    }
+    None
 }
 
 fn read_predicate_info(predicate_info_path: &path::PathBuf) -> BTreeMap<(String, String, String), String> {
@@ -584,47 +606,17 @@ fn read_predicate_info(predicate_info_path: &path::PathBuf) -> BTreeMap<(String,
         .collect()
 }
 
-fn read_category_info(category_info_path: &path::PathBuf) -> BTreeMap<String, String> {
+/// Maps each category to its node file name; an empty `include` keeps every category.
+fn read_category_info(category_info_path: &path::PathBuf, include: &[String]) -> BTreeMap<String, String> {
     let category_file_contents = fs::read_to_string(category_info_path).unwrap();
-
-    let include = vec![
-        "Anatomy", // good
-                  // "BiologicalProcess", // good
-                  // "CellLine" // bad...skip b/c the identifier column is null
-                  // "CellType",          // good...fix: a few lines need to fixed due to newlines
-                  // "CellularComponent", // good
-                  // "Complex", // bad...skip b/c the identifier column is null
-                  // "Compound", // bad...issue with parsing the node file
-                  // "Cytoband", // bad...skip b/c no curie for sequence region...maybe to MONDO???
-                  // "Disease",            // issue with parsing the node file
-                  // "EC",                // good...fix: lots of newlines messing up the columns
-                  // "Food",              // good
-                  // "Gene",              // good
-                  // "Haplotype",         // good
-                  // "MiRNA",             // good
-                  // "MolecularFunction", // good
-                  // "Organism",          // good, however, takes forever to run
-                  // "Pathway",           // good
-                  // "PharmacologicClass", // bad...category not known in NN or Biolink Lookup
-                  // "Protein",       // good, however, takes forever to run
-                  // "ProteinDomain", // good
-                  // "ProteinFamily", // good
-                  // "PwGroup",       // good
-                  // "Reaction",      // bad...skip b/c the identifier column is null
-                  // "SideEffect", // good
-                  // "Symptom",    // good
-                  // "Variant",    // good, however, takes forever to run
-    ];
-
-    // 4,6, 21, 24, 27, 33, 36, 39, 47, 49, 50, 53, 59, 61, 62, 63, 69, 72, 73, 75, 84, 88
     category_file_contents
         .lines()
         .skip(1)
-        .filter(|line| include.iter().any(|l| line.contains(l)))
         .map(|line| {
             let split = line.split('\t').collect_vec();
             (split[0].to_string(), split[1].to_string())
         })
+        .filter(|(category, _)| include.is_empty() || include.contains(category))
         .collect()
 }
 
@@ -679,153 +671,78 @@ async fn create_category_mapping() -> BTreeMap<String, String> {
 }
 
 fn merge_nodes_files(output_path: &path::PathBuf, node_file_names: Vec<path::PathBuf>) {
-    let join_args = JoinArgs::new(JoinType::Full).with_coalesce(JoinCoalesce::CoalesceColumns);
-    let mut main_df = df!(
-        "id" => &Vec::<String>::new(),
-                   "name" => &Vec::<String>::new(),
-                   "category" => &Vec::<String>::new(),
-    )
-    .unwrap();
-
-    for node_file_path in node_file_names.into_iter() {
-        info!("node_file_path: {:?}", node_file_path);
-
-        let df = LazyCsvReader::new(node_file_path.clone())
-            .with_separator(b'\t')
-            .with_truncate_ragged_lines(true)
-            .with_has_header(true)
-            .with_ignore_errors(true)
-            .finish()
-            .unwrap();
-
-        main_df = main_df
-            .clone()
-            .lazy()
-            .join(df.clone(), [col("id"), col("category")], [col("id"), col("category")], join_args.clone())
-            .collect()
-            .expect("Could not join");
-
-        let columns = vec![
-            "name",
-            "source",
-            "description",
-            "license",
-            "url",
-            "synonyms",
-            "vestige",
-            "sources",
-            "accession",
-            "chembl_id",
-            "org_name",
-            "reviewed",
-            "EC",
-            "org_ncbi_id",
-            "gene",
-            "start",
-            "end",
-            "polyprotein",
-            "isoform",
-        ];
-        main_df = rusty_matrix_io::coalesce_columns(main_df, columns);
-
-        println!("column names: {:?}", main_df.get_column_names());
-    }
-
-    let mut file = fs::File::create(output_path.as_path()).unwrap();
-    CsvWriter::new(&mut file).with_separator(b'\t').finish(&mut main_df).unwrap();
+    let frames = node_file_names.iter().map(scan_spoke_file).collect_vec();
+    rusty_matrix_io::sink_tsv(merge_frames(frames, &["id", "category"]), output_path);
+    info!("merged nodes written to: {:?}", output_path);
 }
 
-fn merge_edges(nodes: &path::PathBuf, edges: &path::PathBuf) -> Result<(), Box<dyn error::Error>> {
-    let base_path = path::PathBuf::from("/home/jdr0887/data/matrix/KGs/spoke/V5");
-    let edges_path = base_path.join("edges");
-
-    let edge_files_to_use = vec![
-        "edge_4.tsv",
-        "edge_6.tsv",
-        "edge_7.tsv",
-        "edge_8.tsv",
-        "edge_21.tsv",
-        "edge_24.tsv",
-        "edge_27.tsv",
-        "edge_33.tsv",
-        "edge_36.tsv",
-        "edge_39.tsv",
-        "edge_47.tsv",
-        "edge_49.tsv",
-        "edge_50.tsv",
-        "edge_53.tsv",
-        "edge_56.tsv",
-        "edge_59.tsv",
-        "edge_61.tsv",
-        "edge_62.tsv",
-        "edge_63.tsv",
-        "edge_69.tsv",
-        "edge_72.tsv",
-        "edge_73.tsv",
-        "edge_75.tsv",
-        "edge_84.tsv",
-        "edge_85.tsv",
-        "edge_88.tsv",
-    ];
-
-    let usable_edge_paths = edge_files_to_use.into_iter().map(|a| edges_path.join(a)).collect_vec();
-
-    let output = base_path.join("merged_edges.tsv");
-    merge_edges_files(&output, usable_edge_paths);
+fn merge_edges(edge_files: &[path::PathBuf], output: &path::PathBuf) -> Result<(), Box<dyn error::Error>> {
+    if edge_files.is_empty() {
+        return Err("No edge files to merge, set --edges or edge_files in the config".into());
+    }
+    merge_edges_files(output, edge_files.to_vec());
     Ok(())
 }
 
 fn merge_edges_files(output_path: &path::PathBuf, edge_file_names: Vec<path::PathBuf>) {
-    let join_args = JoinArgs::new(JoinType::Full).with_coalesce(JoinCoalesce::CoalesceColumns);
-    let mut main_df = df!(
-        "subject" => &Vec::<String>::new(),
-                   "predicate" => &Vec::<String>::new(),
-                   "object" => &Vec::<String>::new(),
-    )
-    .unwrap();
-
-    for edge_file_path in edge_file_names.into_iter() {
-        info!("edge_file_path: {:?}", edge_file_path);
-
-        let df = LazyCsvReader::new(edge_file_path.clone())
-            .with_separator(b'\t')
-            .with_truncate_ragged_lines(true)
-            .with_has_header(true)
-            .with_ignore_errors(true)
-            .finish()
-            .unwrap();
+    let frames = edge_file_names.iter().map(scan_spoke_file).collect_vec();
+    rusty_matrix_io::sink_tsv(merge_frames(frames, &["subject", "predicate", "object"]), output_path);
+    info!("merged edges written to: {:?}", output_path);
+}
 
-        main_df = main_df
-            .clone()
-            .lazy()
-            .join(
-                df.clone(),
-                [col("subject"), col("predicate"), col("object")],
-                [col("subject"), col("predicate"), col("object")],
-                join_args.clone(),
-            )
-            .collect()
-            .expect("Could not join");
-
-        let columns = vec![
-            "source",
-            "sources",
-            "unbiased",
-            "evidence",
-            "vestige",
-            "version",
-            "p_value",
-            "direction",
-            "alternative_allele",
-            "reference_allele",
-        ];
-        main_df = rusty_matrix_io::coalesce_columns(main_df, columns);
-
-        println!("column names: {:?}", main_df.get_column_names());
-    }
+fn scan_spoke_file(file_path: &path::PathBuf) -> LazyFrame {
+    info!("scanning: {:?}", file_path);
+    LazyCsvReader::new(file_path.clone())
+        .with_separator(b'\t')
+        .with_truncate_ragged_lines(true)
+        .with_has_header(true)
+        .with_ignore_errors(true)
+        .with_infer_schema_length(Some(0))
+        .finish()
+        .unwrap()
+}
 
-    let mut file = fs::File::create(output_path.as_path()).unwrap();
-    CsvWriter::new(&mut file).with_separator(b'\t').finish(&mut main_df).unwrap();
+/// Stacks the frames over the union of their columns, then collapses rows sharing `keys`, keeping the first non-null value of
+/// every other column. Rows come out in the order their keys are first seen.
+fn merge_frames(mut frames: Vec<LazyFrame>, keys: &[&str]) -> LazyFrame {
+    let schemas = frames.iter_mut().map(|lf| lf.collect_schema().expect("Could not read schema")).collect_vec();
+    let columns = schemas
+        .iter()
+        .flat_map(|schema| schema.iter_names().map(|name| name.to_string()))
+        .unique()
+        .collect_vec();
+    debug!("merged columns: {:?}", columns);
+
+    // frames missing a column get it as nulls so they can be stacked vertically
+    let aligned_frames = frames
+        .into_iter()
+        .zip(schemas.iter())
+        .map(|(lf, schema)| {
+            lf.select(
+                columns
+                    .iter()
+                    .map(|column| match schema.contains(column.as_str()) {
+                        true => col(column.as_str()).cast(DataType::String),
+                        false => lit(NULL).cast(DataType::String).alias(column.as_str()),
+                    })
+                    .collect_vec(),
+            )
+        })
+        .collect_vec();
+
+    let key_exprs = keys.iter().map(|key| col(*key)).collect_vec();
+    let not_null_keys = keys.iter().map(|key| col(*key).is_not_null()).reduce(|acc, expr| acc.and(expr)).unwrap();
+    concat(aligned_frames, UnionArgs::default())
+        .expect("Could not concatenate frames")
+        .filter(not_null_keys)
+        .group_by_stable(key_exprs)
+        .agg(
+            columns
+                .iter()
+                .filter(|column| !keys.contains(&column.as_str()))
+                .map(|column| col(column.as_str()).drop_nulls().first())
+                .collect_vec(),
+        )
 }
 
 // fn main() {
@@ -941,6 +858,120 @@ mod test {
         assert_eq!(true, true);
     }
 
+    #[test]
+    fn test_spoke_config() {
+        let fixture = path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/spoke/test_config.yaml");
+        let config = crate::SpokeConfig::load(&Some(fixture));
+        assert_eq!(config.categories, vec!["Anatomy".to_string(), "Gene".to_string()]);
+        assert_eq!(
+            config.node_files(),
+            vec![
+                path::PathBuf::from("spoke/nodes/node_0_new.tsv"),
+                path::PathBuf::from("/elsewhere/node_1_new.tsv")
+            ]
+        );
+        assert_eq!(config.edge_files(), vec![path::PathBuf::from("spoke/edges/edge_4.tsv")]);
+        assert_eq!(config.nodes_output(), Some(path::PathBuf::from("spoke/merged_nodes.tsv")));
+        assert_eq!(config.edges_output(), None);
+
+        // the example config has to parse and stay free of machine-specific paths
+        let example: crate::SpokeConfig = serde_yml::from_str(include_str!("../../resources/spoke/config.yaml")).unwrap();
+        assert!(example.base_path.unwrap().is_relative());
+    }
+
+    #[test]
+    fn test_clean_edges() {
+        let edges = "start_id\tend_id\ttype\tsources\n1\t2\tTREATS_CtD\tdrugcentral\n3\t99\tUPREGULATES_AuG\t\n1\t3\tMADE_UP_CmA\tx\n";
+        let id_identifier_map: HashMap<String, String> = [("1", "CHEBI:1"), ("2", "MONDO:0000002"), ("3", "UBERON:0000003")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let predicate_mappings = crate::load_predicate_mappings(&None);
+
+        let mut output = Vec::new();
+        let counts = crate::clean_edges(BufReader::new(edges.as_bytes()), &mut output, &id_identifier_map, &predicate_mappings);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.lines().collect_vec(),
+            vec![
+                "subject\tobject\tpredicate\tsources\tsubject_aspect_qualifier\tsubject_direction_qualifier\tobject_aspect_qualifier\tobject_direction_qualifier\tqualified_predicate",
+                "CHEBI:1\tMONDO:0000002\tbiolink:treats\tdrugcentral\t\t\t\t\t",
+                "UBERON:0000003\t99\tbiolink:affects\t\t\t\texpression\tincreased\tbiolink:causes",
+                "CHEBI:1\tUBERON:0000003\tMADE_UP_CmA\tx\t\t\t\t\t",
+            ]
+        );
+        assert_eq!(counts.edges, 3);
+        assert_eq!(counts.remapped, 5);
+        assert_eq!(counts.unresolved, 1);
+        assert_eq!(counts.unmapped_predicates.get("MADE_UP_CmA"), Some(&1));
+
+        // cleaning an already cleaned file rewrites the qualifier columns instead of appending them again
+        let mut second_output = Vec::new();
+        crate::clean_edges(BufReader::new(output.as_bytes()), &mut second_output, &id_identifier_map, &predicate_mappings);
+        assert_eq!(String::from_utf8(second_output).unwrap(), output);
+
+        // a file without extra columns goes straight from predicate to the qualifier columns
+        let bare_edges = "start_id\tend_id\ttype\n1\t2\tTREATS_CtD\n";
+        let mut bare_output = Vec::new();
+        crate::clean_edges(BufReader::new(bare_edges.as_bytes()), &mut bare_output, &id_identifier_map, &predicate_mappings);
+        assert_eq!(
+            String::from_utf8(bare_output).unwrap().lines().collect_vec(),
+            vec![
+                "subject\tobject\tpredicate\tsubject_aspect_qualifier\tsubject_direction_qualifier\tobject_aspect_qualifier\tobject_direction_qualifier\tqualified_predicate",
+                "CHEBI:1\tMONDO:0000002\tbiolink:treats\t\t\t\t\t",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_frames() {
+        let first = df!(
+            "id" => ["1", "2", "3"],
+            "category" => ["Gene", "Gene", "Compound"],
+            "name" => [Some("BRCA1"), None, Some("aspirin")],
+        )
+        .unwrap();
+        let second = df!(
+            "id" => [Some("2"), Some("1"), Some("3"), None],
+            "category" => ["Gene", "Gene", "Disease", "Gene"],
+            "name" => [Some("TP53"), Some("ignored"), Some("asthma"), Some("orphan")],
+            "synonyms" => [Some("p53"), None, None, Some("x")],
+        )
+        .unwrap();
+
+        let merge = || {
+            crate::merge_frames(vec![first.clone().lazy(), second.clone().lazy()], &["id", "category"])
+                .collect()
+                .unwrap()
+        };
+        let expected = df!(
+            "id" => ["1", "2", "3", "3"],
+            "category" => ["Gene", "Gene", "Compound", "Disease"],
+            "name" => ["BRCA1", "TP53", "aspirin", "asthma"],
+            "synonyms" => [None, Some("p53"), None, None],
+        )
+        .unwrap();
+        // rows keep the order their keys are first seen, run after run
+        assert_eq!(merge(), expected);
+        assert!((0..5).all(|_| merge() == expected));
+    }
+
+    #[test]
+    fn test_merge_nodes_files() {
+        let test_dir = std::env::temp_dir().join("spoke_test_merge_nodes_files");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let first = test_dir.join("first.tsv");
+        std::fs::write(&first, "id\tcategory\tname\n2\tGene\t\n1\tGene\tBRCA1\n").unwrap();
+        let second = test_dir.join("second.tsv");
+        std::fs::write(&second, "id\tcategory\tname\n1\tGene\tignored\n2\tGene\tTP53\n").unwrap();
+
+        let output = test_dir.join("nodes.tsv");
+        crate::merge_nodes_files(&output, vec![first, second]);
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "id\tcategory\tname\n2\tGene\tTP53\n1\tGene\tBRCA1\n");
+
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+
     #[test]
     fn test_read_nodes_file() {
         let base_path = path::PathBuf::from("/media/jdr0887/backup/home/jdr0887/matrix/KGs/spoke/V5");
@@ -1,9 +1,15 @@
 extern crate env_logger;
 extern crate log;
 
+use clap::ValueEnum;
+use log::{debug, warn};
 use polars::prelude::*;
 use polars::prelude::{coalesce, IntoLazy};
+use rand::rngs::StdRng;
+use rand::seq;
 use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::{fs, io, path};
 
 #[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize, Ord, PartialOrd)]
@@ -76,3 +82,259 @@ pub fn read_nodes_file(nodes_path: &path::PathBuf) -> Vec<Node> {
     }
     nodes
 }
+
+pub fn sha256_digest(path: &path::PathBuf) -> String {
+    let file = fs::File::open(path).unwrap();
+    let mut reader = io::BufReader::with_capacity(2_usize.pow(16), file);
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher).expect("Could not hash file");
+    format!("{:x}", hasher.finalize())
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileFormat {
+    #[default]
+    Tsv,
+    Parquet,
+    Jsonl,
+}
+
+impl FileFormat {
+    pub fn extension(&self) -> &str {
+        match self {
+            FileFormat::Tsv => "tsv",
+            FileFormat::Parquet => "parquet",
+            FileFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+pub fn scan_kg_file(path: &path::PathBuf) -> LazyFrame {
+    let file_name = path.file_name().unwrap().to_string_lossy().to_lowercase();
+    if file_name.ends_with(".parquet") {
+        LazyFrame::scan_parquet(path, ScanArgsParquet::default()).unwrap()
+    } else if file_name.ends_with(".jsonl") || file_name.ends_with(".ndjson") {
+        LazyJsonLineReader::new(path).finish().unwrap()
+    } else {
+        LazyCsvReader::new(path)
+            .with_separator(b'\t')
+            .with_truncate_ragged_lines(true)
+            .with_has_header(true)
+            .with_ignore_errors(true)
+            .finish()
+            .unwrap()
+    }
+}
+
+pub fn write_dataframe(df: &mut DataFrame, output: &path::PathBuf, format: FileFormat) {
+    debug!("writing {:?} as {:?} with shape {:?}", output, format, df.shape());
+    let mut file = fs::File::create(output.as_path()).unwrap();
+    match format {
+        FileFormat::Tsv => {
+            CsvWriter::new(&mut file).with_separator(b'\t').finish(df).unwrap();
+        }
+        FileFormat::Parquet => {
+            ParquetWriter::new(&mut file).finish(df).unwrap();
+        }
+        FileFormat::Jsonl => {
+            JsonWriter::new(&mut file).with_json_format(JsonFormat::JsonLines).finish(df).unwrap();
+        }
+    }
+}
+
+pub fn count_by(df: &DataFrame, column_name: &str) -> Option<BTreeMap<String, usize>> {
+    count_by_lazy(df.clone().lazy(), column_name)
+}
+
+pub fn count_by_lazy(mut lf: LazyFrame, column_name: &str) -> Option<BTreeMap<String, usize>> {
+    if !lf.collect_schema().unwrap().contains(column_name) {
+        return None;
+    }
+
+    let counts_df = lf
+        .group_by([col(column_name).cast(DataType::String).fill_null(lit("null"))])
+        .agg([len().alias("count")])
+        .with_new_streaming(true)
+        .collect()
+        .unwrap();
+
+    let values = counts_df.column(column_name).unwrap().str().unwrap().clone();
+    let counts = counts_df.column("count").unwrap().cast(&DataType::UInt64).unwrap().u64().unwrap().clone();
+
+    Some(
+        values
+            .into_iter()
+            .zip(&counts)
+            .filter_map(|(v, c)| match (v, c) {
+                (Some(v), Some(c)) => Some((v.to_string(), c as usize)),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+pub fn count_rows(lf: LazyFrame) -> usize {
+    let count_df = lf.select([len().alias("count")]).with_new_streaming(true).collect().unwrap();
+    count_df.column("count").unwrap().get(0).unwrap().extract::<u64>().unwrap_or(0) as usize
+}
+
+/// Streams the frame to a TSV file without materializing it in memory.
+pub fn sink_tsv(lf: LazyFrame, output: &path::PathBuf) {
+    debug!("sinking {:?}", output);
+    let mut options = CsvWriterOptions::default();
+    options.serialize_options.separator = b'\t';
+    lf.sink_csv(SinkTarget::Path(Arc::new(output.clone())), options, None, SinkOptions::default())
+        .unwrap()
+        .with_new_streaming(true)
+        .collect()
+        .unwrap();
+}
+
+pub fn allocate_quotas(
+    stratum_sizes: &BTreeMap<String, usize>,
+    size: usize,
+    min_per_stratum: usize,
+    quotas: &BTreeMap<String, usize>,
+) -> BTreeMap<String, usize> {
+    let total: usize = stratum_sizes.values().sum();
+    stratum_sizes
+        .iter()
+        .map(|(stratum, stratum_size)| {
+            let allocation = match quotas.get(stratum) {
+                Some(quota) => *quota,
+                None => {
+                    let proportional = (size as f64 * *stratum_size as f64 / total.max(1) as f64).round() as usize;
+                    proportional.max(min_per_stratum)
+                }
+            };
+            (stratum.clone(), allocation.min(*stratum_size))
+        })
+        .collect()
+}
+
+pub fn sample_stratified(
+    df: &DataFrame,
+    stratum_column: &str,
+    size: usize,
+    min_per_stratum: usize,
+    quotas: &BTreeMap<String, usize>,
+    rng: &mut StdRng,
+) -> DataFrame {
+    let stratum_values = df.column(stratum_column).unwrap().cast(&DataType::String).unwrap();
+
+    let mut stratum_indexes: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    stratum_values.str().unwrap().into_iter().enumerate().for_each(|(idx, value)| {
+        stratum_indexes.entry(value.unwrap_or("null").to_string()).or_default().push(idx);
+    });
+
+    let stratum_sizes = stratum_indexes.iter().map(|(k, v)| (k.clone(), v.len())).collect();
+    let allocation = allocate_quotas(&stratum_sizes, size, min_per_stratum, quotas);
+    debug!("{} allocation: {:?}", stratum_column, allocation);
+
+    quotas
+        .keys()
+        .filter(|k| !stratum_indexes.contains_key(*k))
+        .for_each(|k| warn!("quota for unknown stratum: {}", k));
+
+    let mut sampled_indexes = stratum_indexes
+        .iter()
+        .flat_map(|(stratum, indexes)| {
+            let amount = *allocation.get(stratum).unwrap();
+            seq::index::sample(rng, indexes.len(), amount)
+                .into_iter()
+                .map(|i| indexes[i] as IdxSize)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    sampled_indexes.sort();
+
+    df.take(&IdxCa::from_vec("idx".into(), sampled_indexes)).unwrap()
+}
+
+pub fn close_subgraph(edges_df: DataFrame, nodes_df: &DataFrame) -> DataFrame {
+    let node_ids = nodes_df.clone().lazy().select([col("id")]);
+
+    // edges whose endpoints are absent from the nodes file would leave the sample open, so they are dropped
+    let closed_edges_df = edges_df
+        .clone()
+        .lazy()
+        .semi_join(node_ids.clone(), col("subject"), col("id"))
+        .semi_join(node_ids.clone(), col("object"), col("id"))
+        .collect()
+        .unwrap();
+
+    let dangling_edges = edges_df.height() - closed_edges_df.height();
+    if dangling_edges > 0 {
+        warn!("dropped {} edges with an endpoint missing from the nodes file", dangling_edges);
+    }
+
+    let dangling_ids_df = concat(
+        [
+            closed_edges_df.clone().lazy().select([col("subject").alias("id")]),
+            closed_edges_df.clone().lazy().select([col("object").alias("id")]),
+        ],
+        UnionArgs::default(),
+    )
+    .unwrap()
+    .anti_join(node_ids, col("id"), col("id"))
+    .collect()
+    .unwrap();
+    assert_eq!(dangling_ids_df.height(), 0, "subgraph is not closed");
+
+    closed_edges_df
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Adjacency {
+    pub node_ids: Vec<String>,
+    pub node_index: HashMap<String, usize>,
+    pub edges: Vec<(usize, usize)>,
+    pub incident: Vec<Vec<usize>>,
+}
+
+impl Adjacency {
+    pub fn from_edges(edges_df: &DataFrame) -> Adjacency {
+        let subjects = edges_df.column("subject").unwrap().str().unwrap().clone();
+        let objects = edges_df.column("object").unwrap().str().unwrap().clone();
+
+        let mut adjacency = Adjacency::default();
+        for (subject, object) in subjects.into_iter().zip(&objects) {
+            let subject_idx = adjacency.get_or_insert(subject.unwrap_or(""));
+            let object_idx = adjacency.get_or_insert(object.unwrap_or(""));
+            let edge_idx = adjacency.edges.len();
+            adjacency.edges.push((subject_idx, object_idx));
+            adjacency.incident[subject_idx].push(edge_idx);
+            if subject_idx != object_idx {
+                adjacency.incident[object_idx].push(edge_idx);
+            }
+        }
+        adjacency
+    }
+
+    fn get_or_insert(&mut self, id: &str) -> usize {
+        match self.node_index.get(id) {
+            Some(idx) => *idx,
+            None => {
+                let idx = self.node_ids.len();
+                self.node_ids.push(id.to_string());
+                self.node_index.insert(id.to_string(), idx);
+                self.incident.push(vec![]);
+                idx
+            }
+        }
+    }
+
+    pub fn degree(&self, node: usize) -> usize {
+        self.incident[node].len()
+    }
+
+    pub fn neighbor(&self, node: usize, edge: usize) -> usize {
+        let (subject, object) = self.edges[edge];
+        if subject == node {
+            object
+        } else {
+            subject
+        }
+    }
+}